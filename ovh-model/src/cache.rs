@@ -0,0 +1,298 @@
+//! Compact binary cache of parsed snapshots.
+//!
+//! Parsing the YAML files is by far the most expensive part of an analysis.
+//! The first time a snapshot is parsed, it is also stored in the cache directory
+//! in a compact binary form where router names and labels are interned.
+//! Later runs load this form instead, as long as the source file keeps
+//! the same size and modification time.
+
+use crate::{FileMetadata, Link, OvhData, ParseError, Router};
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+/// Bumped each time the on-disk layout changes, so that old entries are ignored.
+const CACHE_VERSION: u32 = 1;
+
+/// Size and modification time of the source file when the entry was written.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+struct SourceStamp {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+}
+
+impl SourceStamp {
+    fn of(path: &str) -> io::Result<SourceStamp> {
+        let metadata = fs::metadata(path)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(SourceStamp {
+            size: metadata.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+        })
+    }
+}
+
+/// One link, with the router, peer and label replaced by their index in `CachedSnapshot::names`.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedLink {
+    router: u32,
+    peer: u32,
+    label: u32,
+    load: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedSnapshot {
+    version: u32,
+    /// Location of the source snapshot, to detect collisions of cache file names.
+    source: String,
+    stamp: SourceStamp,
+    /// Interned router names and labels.
+    names: Vec<String>,
+    /// Routers without any link, which would otherwise be lost.
+    routers: Vec<u32>,
+    links: Vec<CachedLink>,
+}
+
+impl CachedSnapshot {
+    fn from_data(source: String, stamp: SourceStamp, data: &OvhData) -> CachedSnapshot {
+        let mut names = Interner::default();
+
+        let mut router_names = data.data.keys().collect::<Vec<&String>>();
+        router_names.sort();
+        let mut routers = Vec::with_capacity(router_names.len());
+        let mut links = Vec::new();
+        for router_name in router_names {
+            let router_idx = names.intern(router_name);
+            routers.push(router_idx);
+            let router = &data.data[router_name];
+            let mut peers = router.peers.iter().collect::<Vec<(&String, &Vec<Link>)>>();
+            peers.sort_by_key(|(peer_name, _)| *peer_name);
+            for (peer_name, peer_links) in peers {
+                let peer_idx = names.intern(peer_name);
+                for link in peer_links {
+                    links.push(CachedLink {
+                        router: router_idx,
+                        peer: peer_idx,
+                        label: names.intern(&link.label),
+                        load: link.load,
+                    });
+                }
+            }
+        }
+
+        CachedSnapshot {
+            version: CACHE_VERSION,
+            source,
+            stamp,
+            names: names.names,
+            routers,
+            links,
+        }
+    }
+
+    fn into_data(self, file: &FileMetadata) -> Option<OvhData> {
+        let name = |idx: u32| self.names.get(idx as usize);
+        let mut data: HashMap<String, Router> = HashMap::with_capacity(self.routers.len());
+        for &router in self.routers.iter() {
+            let router_name = name(router)?;
+            data.insert(
+                router_name.to_string(),
+                Router {
+                    name: router_name.to_string(),
+                    peers: HashMap::new(),
+                },
+            );
+        }
+        for link in self.links.iter() {
+            let router = data.get_mut(name(link.router)?)?;
+            router
+                .peers
+                .entry(name(link.peer)?.to_string())
+                .or_default()
+                .push(Link {
+                    label: name(link.label)?.to_string(),
+                    load: link.load,
+                });
+        }
+
+        Some(OvhData {
+            timestamp: file.timestamp,
+            data,
+        })
+    }
+}
+
+/// Gives each distinct string an index, in order of first appearance.
+#[derive(Default)]
+struct Interner {
+    names: Vec<String>,
+    index: HashMap<String, u32>,
+}
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(&idx) = self.index.get(name) {
+            return idx;
+        }
+        let idx = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.index.insert(name.to_string(), idx);
+        idx
+    }
+}
+
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new().with_varint_encoding()
+}
+
+/// Directory holding one binary cache entry per snapshot.
+/// Clones share the same record of failed writes.
+#[derive(Debug, Clone)]
+pub struct SnapshotCache {
+    directory: PathBuf,
+    store_failures: Arc<Mutex<Vec<String>>>,
+}
+
+impl SnapshotCache {
+    /// Uses `directory` as cache, creating it if needed.
+    pub fn new<P: AsRef<Path>>(directory: P) -> io::Result<SnapshotCache> {
+        fs::create_dir_all(directory.as_ref())?;
+        Ok(SnapshotCache {
+            directory: directory.as_ref().to_path_buf(),
+            store_failures: Arc::default(),
+        })
+    }
+
+    fn entry_path(&self, file: &FileMetadata) -> PathBuf {
+        let name = file
+            .display_path()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        self.directory.join(format!("{}.bin", name))
+    }
+
+    /// Loads the cached snapshot, if it exists and is still up to date with its source file.
+    /// A corrupted entry is a cache miss.
+    pub fn load(&self, file: &FileMetadata) -> Option<OvhData> {
+        let stamp = SourceStamp::of(&file.filepath).ok()?;
+        let fd = fs::File::open(self.entry_path(file)).ok()?;
+        let limit = fd.metadata().ok()?.len();
+        let cached: CachedSnapshot = bincode_options()
+            .with_limit(limit)
+            .deserialize_from(BufReader::new(fd))
+            .ok()?;
+        if cached.version != CACHE_VERSION
+            || cached.stamp != stamp
+            || cached.source != file.display_path()
+        {
+            return None;
+        }
+        cached.into_data(file)
+    }
+
+    /// Stores a parsed snapshot in the cache.
+    /// The entry is written to a temporary file first, so that concurrent readers never see partial entries.
+    pub fn store(&self, file: &FileMetadata, data: &OvhData) -> Result<(), Box<dyn Error>> {
+        let stamp = SourceStamp::of(&file.filepath)?;
+        let cached = CachedSnapshot::from_data(file.display_path(), stamp, data);
+        let path = self.entry_path(file);
+        let tmp_path = path.with_extension("bin.tmp");
+        let mut wrt = BufWriter::new(fs::File::create(&tmp_path)?);
+        bincode_options().serialize_into(&mut wrt, &cached)?;
+        wrt.into_inner().map_err(|e| e.into_error())?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Returns the cached snapshot if it is up to date, or parses the source file and caches the result.
+    /// Failing to write the cache is not an error: the parsed snapshot is returned anyway,
+    /// and the failure is kept for [`SnapshotCache::take_store_failures`].
+    pub fn parse(&self, file: &FileMetadata) -> Result<OvhData, ParseError> {
+        if let Some(data) = self.load(file) {
+            return Ok(data);
+        }
+        let data = file.parse()?;
+        if let Err(e) = self.store(file, &data) {
+            self.store_failures
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(format!("{}: {}", file.display_path(), e));
+        }
+        Ok(data)
+    }
+
+    /// Returns the entries that [`SnapshotCache::parse`] could not write since the last call,
+    /// as `<source file>: <error>` messages.
+    pub fn take_store_failures(&self) -> Vec<String> {
+        std::mem::take(
+            &mut *self
+                .store_failures
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_round_trip_and_invalidation() {
+        let dir = std::env::temp_dir().join(format!("ovh-model-cache-{}", std::process::id()));
+        let source = dir.join("europe_1650000000.yaml");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            &source,
+            "a:\n  links:\n  - {label: '#1', load: 12, peer: b}\n  - {label: '#2', load: 13, peer: b}\nb:\n  links: []\n",
+        )
+        .unwrap();
+        let file = FileMetadata::path_to_file_metadata(&source).unwrap();
+        let cache = SnapshotCache::new(dir.join("cache")).unwrap();
+
+        assert!(cache.load(&file).is_none());
+        let parsed = cache.parse(&file).unwrap();
+        let cached = cache.load(&file).unwrap();
+        assert_eq!(cached.to_snapshot(), parsed.to_snapshot());
+        assert_eq!(cached.timestamp, file.timestamp);
+        assert!(cached.data["b"].peers.is_empty());
+
+        // Changing the source file invalidates the entry
+        fs::write(&source, "a:\n  links: []\n").unwrap();
+        assert!(cache.load(&file).is_none());
+        assert_eq!(cache.parse(&file).unwrap().data.len(), 1);
+
+        // A corrupted entry is a miss, even with a huge length prefix
+        fs::write(cache.entry_path(&file), [0xff; 11]).unwrap();
+        assert!(cache.load(&file).is_none());
+        assert_eq!(cache.parse(&file).unwrap().data.len(), 1);
+        assert!(cache.take_store_failures().is_empty());
+
+        // Failing to write the entry is recorded, the snapshot is still returned
+        fs::remove_dir_all(dir.join("cache")).unwrap();
+        assert_eq!(cache.parse(&file).unwrap().data.len(), 1);
+        assert_eq!(cache.take_store_failures().len(), 1);
+        assert!(cache.take_store_failures().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
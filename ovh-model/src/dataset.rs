@@ -0,0 +1,265 @@
+//! Discovery of the snapshots of the whole dataset.
+//!
+//! The dataset is made of one directory tree per weather map. A `MapConfig` tells
+//! which map a directory belongs to; every snapshot found below that directory
+//! (plain, compressed or inside a tar archive) is assigned to the map.
+
+use crate::get_files::get_path_files;
+use crate::timestamp::{SkippedFile, TimestampExtractor};
+use crate::FileMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The weather maps published by OVH.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OvhMap {
+    Europe,
+    World,
+    NorthAmerica,
+    AsiaPacific,
+}
+
+impl OvhMap {
+    pub const ALL: [OvhMap; 4] = [
+        OvhMap::Europe,
+        OvhMap::World,
+        OvhMap::NorthAmerica,
+        OvhMap::AsiaPacific,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OvhMap::Europe => "europe",
+            OvhMap::World => "world",
+            OvhMap::NorthAmerica => "north-america",
+            OvhMap::AsiaPacific => "asia-pacific",
+        }
+    }
+}
+
+impl fmt::Display for OvhMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OvhMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OvhMap, String> {
+        match s.to_lowercase().as_str() {
+            "europe" | "eu" => Ok(OvhMap::Europe),
+            "world" => Ok(OvhMap::World),
+            "north-america" | "usa" | "na" => Ok(OvhMap::NorthAmerica),
+            "asia-pacific" | "apac" => Ok(OvhMap::AsiaPacific),
+            _ => Err(format!("Unknown map: {}", s)),
+        }
+    }
+}
+
+/// Which map each directory of the dataset belongs to, by directory name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapConfig {
+    pub directories: HashMap<String, OvhMap>,
+}
+
+impl Default for MapConfig {
+    /// The layout used to collect the dataset: `data`, `data_world`, `data_usa` and `data_apac`.
+    fn default() -> MapConfig {
+        MapConfig {
+            directories: HashMap::from([
+                ("data".to_string(), OvhMap::Europe),
+                ("data_world".to_string(), OvhMap::World),
+                ("data_usa".to_string(), OvhMap::NorthAmerica),
+                ("data_apac".to_string(), OvhMap::AsiaPacific),
+            ]),
+        }
+    }
+}
+
+impl MapConfig {
+    /// Reads a mapping such as `{data: europe, data_world: world}` from a YAML file.
+    pub fn from_yaml_file(filepath: &str) -> Result<MapConfig, Box<dyn std::error::Error>> {
+        let fd = fs::File::open(filepath)?;
+        let directories: HashMap<String, OvhMap> = serde_yaml::from_reader(fd)?;
+        Ok(MapConfig { directories })
+    }
+
+    /// Map of `path`, given by its closest ancestor (or itself) listed in the mapping.
+    pub fn map_of(&self, path: &Path) -> Option<OvhMap> {
+        path.ancestors()
+            .filter_map(|p| p.file_name()?.to_str())
+            .find_map(|name| self.directories.get(name).copied())
+    }
+}
+
+/// All the snapshots of the dataset, per map and sorted by timestamp.
+#[derive(Debug, Default)]
+pub struct Dataset {
+    pub root: PathBuf,
+    files: BTreeMap<OvhMap, Vec<FileMetadata>>,
+    /// Snapshots found outside of any mapped directory.
+    pub unassigned: Vec<FileMetadata>,
+    /// Files that are not snapshots, or whose timestamp could not be found.
+    pub skipped: Vec<SkippedFile>,
+}
+
+impl Dataset {
+    /// Walks `root` recursively and assigns every snapshot to its map,
+    /// with the default timestamp patterns.
+    pub fn discover<P: AsRef<Path>>(root: P, config: &MapConfig) -> io::Result<Dataset> {
+        Dataset::discover_with(root, config, &TimestampExtractor::default())
+    }
+
+    /// Walks `root` recursively and assigns every snapshot to its map.
+    /// The manifest of each directory, if any, is added to `extractor` for the files of this directory.
+    /// Symbolic links to directories are not followed, those to files are.
+    pub fn discover_with<P: AsRef<Path>>(
+        root: P,
+        config: &MapConfig,
+        extractor: &TimestampExtractor,
+    ) -> io::Result<Dataset> {
+        let root = root.as_ref();
+        let mut dataset = Dataset {
+            root: root.to_path_buf(),
+            ..Default::default()
+        };
+
+        let mut to_visit = vec![root.to_path_buf()];
+        while let Some(dir) = to_visit.pop() {
+            let extractor = extractor.for_directory(&dir)?;
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                let path = entry.path();
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    to_visit.push(path);
+                    continue;
+                }
+                // A link to a directory may point to one of its ancestors
+                if file_type.is_symlink() && path.is_dir() {
+                    continue;
+                }
+                let files = get_path_files(&path, &extractor, &mut dataset.skipped)?;
+                if files.is_empty() {
+                    continue;
+                }
+                // Only consider the directories below the root for the mapping,
+                // except the root itself which may be a map directory
+                let relative = match path.strip_prefix(root) {
+                    Ok(relative) => root
+                        .file_name()
+                        .map(|name| Path::new(name).join(relative))
+                        .unwrap_or_else(|| relative.to_path_buf()),
+                    Err(_) => path.to_path_buf(),
+                };
+                let parent = relative.parent().unwrap_or_else(|| Path::new(""));
+                match config.map_of(parent) {
+                    Some(map) => dataset.files.entry(map).or_default().extend(files),
+                    None => dataset.unassigned.extend(files),
+                }
+            }
+        }
+
+        for files in dataset.files.values_mut() {
+            files.sort_by_key(|f| f.timestamp);
+        }
+        dataset.unassigned.sort_by_key(|f| f.timestamp);
+        dataset.skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(dataset)
+    }
+
+    /// Maps with at least one snapshot.
+    pub fn maps(&self) -> Vec<OvhMap> {
+        self.files.keys().copied().collect()
+    }
+
+    /// Snapshots of `map`, sorted by timestamp.
+    pub fn files(&self, map: OvhMap) -> &[FileMetadata] {
+        self.files.get(&map).map(|f| f.as_slice()).unwrap_or(&[])
+    }
+
+    /// Iterates over the maps and their sorted snapshots.
+    pub fn iter(&self) -> impl Iterator<Item = (OvhMap, &[FileMetadata])> {
+        self.files
+            .iter()
+            .map(|(map, files)| (*map, files.as_slice()))
+    }
+
+    /// Total number of snapshots assigned to a map.
+    pub fn len(&self) -> usize {
+        self.files.values().map(|f| f.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamp::SkipReason;
+
+    #[test]
+    fn test_discover() {
+        let root = std::env::temp_dir().join(format!("ovh-model-dataset-{}", std::process::id()));
+        let snapshot = "a:\n  links: []\n";
+        for (dir, file) in [
+            ("data/2021", "europe_1650000300.yaml"),
+            ("data/2020", "europe_1650000000.yaml"),
+            ("data_usa", "usa_1650000000.yaml"),
+            ("other", "x_1650000000.yaml"),
+            ("data_apac", "README.md"),
+        ] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            fs::write(root.join(dir).join(file), snapshot).unwrap();
+        }
+
+        let dataset = Dataset::discover(&root, &MapConfig::default()).unwrap();
+        assert_eq!(dataset.maps(), vec![OvhMap::Europe, OvhMap::NorthAmerica]);
+        let europe = dataset.files(OvhMap::Europe);
+        assert_eq!(europe.len(), 2);
+        assert!(europe[0].timestamp < europe[1].timestamp);
+        assert_eq!(dataset.files(OvhMap::World).len(), 0);
+        assert_eq!(dataset.unassigned.len(), 1);
+        assert_eq!(dataset.skipped.len(), 1);
+        assert_eq!(dataset.skipped[0].reason, SkipReason::NotASnapshot);
+
+        // The root itself can be a map directory
+        let europe_only = Dataset::discover(root.join("data"), &MapConfig::default()).unwrap();
+        assert_eq!(europe_only.files(OvhMap::Europe).len(), 2);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_discover_symlinks() {
+        let root = std::env::temp_dir().join(format!("ovh-model-symlinks-{}", std::process::id()));
+        fs::create_dir_all(root.join("data")).unwrap();
+        fs::write(
+            root.join("data/europe_1650000000.yaml"),
+            "a:\n  links: []\n",
+        )
+        .unwrap();
+        // A loop back to the root, and a link to a snapshot
+        std::os::unix::fs::symlink(&root, root.join("data/loop")).unwrap();
+        std::os::unix::fs::symlink(
+            root.join("data/europe_1650000000.yaml"),
+            root.join("data/europe_1650000300.yaml"),
+        )
+        .unwrap();
+
+        let dataset = Dataset::discover(&root, &MapConfig::default()).unwrap();
+        assert_eq!(dataset.files(OvhMap::Europe).len(), 2);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
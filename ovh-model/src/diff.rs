@@ -0,0 +1,339 @@
+//! Changes between two snapshots of the same map.
+
+use crate::{Link, OvhData, Router};
+use chrono::prelude::NaiveDateTime;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// Load variation, in percentage points, from which a link is reported by `OvhData::diff`.
+pub const DEFAULT_LOAD_THRESHOLD: u32 = 20;
+
+/// One change between two snapshots. Peer adjacencies are directed, from `router` to `peer`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Change {
+    RouterAdded {
+        router: String,
+    },
+    RouterRemoved {
+        router: String,
+    },
+    PeerAdded {
+        router: String,
+        peer: String,
+        nb_links: usize,
+    },
+    PeerRemoved {
+        router: String,
+        peer: String,
+        nb_links: usize,
+    },
+    /// The number of parallel links towards the peer changed.
+    EcmpSizeChanged {
+        router: String,
+        peer: String,
+        before: usize,
+        after: usize,
+    },
+    /// Some labels of the links towards the peer appeared or disappeared.
+    LabelsChanged {
+        router: String,
+        peer: String,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    /// The load of a link changed by at least the threshold.
+    LoadChanged {
+        router: String,
+        peer: String,
+        label: String,
+        before: u32,
+        after: u32,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::RouterAdded { router } => write!(f, "+ router {}", router),
+            Change::RouterRemoved { router } => write!(f, "- router {}", router),
+            Change::PeerAdded {
+                router,
+                peer,
+                nb_links,
+            } => write!(f, "+ peer {} -> {} ({} links)", router, peer, nb_links),
+            Change::PeerRemoved {
+                router,
+                peer,
+                nb_links,
+            } => write!(f, "- peer {} -> {} ({} links)", router, peer, nb_links),
+            Change::EcmpSizeChanged {
+                router,
+                peer,
+                before,
+                after,
+            } => write!(
+                f,
+                "~ ecmp {} -> {}: {} -> {} links",
+                router, peer, before, after
+            ),
+            Change::LabelsChanged {
+                router,
+                peer,
+                added,
+                removed,
+            } => {
+                write!(f, "~ labels {} -> {}:", router, peer)?;
+                for label in added {
+                    write!(f, " +{}", label)?;
+                }
+                for label in removed {
+                    write!(f, " -{}", label)?;
+                }
+                Ok(())
+            }
+            Change::LoadChanged {
+                router,
+                peer,
+                label,
+                before,
+                after,
+            } => write!(
+                f,
+                "~ load {} -> {} {}: {}% -> {}%",
+                router, peer, label, before, after
+            ),
+        }
+    }
+}
+
+/// All the changes from the snapshot at `from` to the one at `to`, sorted by router and peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    pub from: NaiveDateTime,
+    pub to: NaiveDateTime,
+    pub changes: Vec<Change>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl OvhData {
+    /// Changes from `self` to `other`, reporting the loads that vary by at least
+    /// `DEFAULT_LOAD_THRESHOLD` percentage points.
+    pub fn diff(&self, other: &OvhData) -> SnapshotDiff {
+        self.diff_with(other, DEFAULT_LOAD_THRESHOLD)
+    }
+
+    /// Changes from `self` to `other`, reporting the loads that vary by at least
+    /// `load_threshold` percentage points.
+    pub fn diff_with(&self, other: &OvhData, load_threshold: u32) -> SnapshotDiff {
+        let mut changes = Vec::new();
+        let routers = self
+            .data
+            .keys()
+            .chain(other.data.keys())
+            .collect::<BTreeSet<&String>>();
+        for router_name in routers {
+            match (self.data.get(router_name), other.data.get(router_name)) {
+                (Some(before), Some(after)) => {
+                    diff_router(router_name, before, after, load_threshold, &mut changes)
+                }
+                (None, Some(after)) => {
+                    changes.push(Change::RouterAdded {
+                        router: router_name.to_string(),
+                    });
+                    for (peer, links) in sorted_peers(after) {
+                        changes.push(Change::PeerAdded {
+                            router: router_name.to_string(),
+                            peer: peer.to_string(),
+                            nb_links: links.len(),
+                        });
+                    }
+                }
+                (Some(before), None) => {
+                    changes.push(Change::RouterRemoved {
+                        router: router_name.to_string(),
+                    });
+                    for (peer, links) in sorted_peers(before) {
+                        changes.push(Change::PeerRemoved {
+                            router: router_name.to_string(),
+                            peer: peer.to_string(),
+                            nb_links: links.len(),
+                        });
+                    }
+                }
+                (None, None) => unreachable!(),
+            }
+        }
+
+        SnapshotDiff {
+            from: self.timestamp,
+            to: other.timestamp,
+            changes,
+        }
+    }
+}
+
+fn sorted_peers(router: &Router) -> Vec<(&String, &Vec<Link>)> {
+    let mut peers = router.peers.iter().collect::<Vec<(&String, &Vec<Link>)>>();
+    peers.sort_by_key(|(peer_name, _)| *peer_name);
+    peers
+}
+
+fn diff_router(
+    router_name: &str,
+    before: &Router,
+    after: &Router,
+    load_threshold: u32,
+    changes: &mut Vec<Change>,
+) {
+    let peers = before
+        .peers
+        .keys()
+        .chain(after.peers.keys())
+        .collect::<BTreeSet<&String>>();
+    for peer in peers {
+        let (links_before, links_after) = match (before.peers.get(peer), after.peers.get(peer)) {
+            (Some(b), Some(a)) => (b, a),
+            (None, Some(a)) => {
+                changes.push(Change::PeerAdded {
+                    router: router_name.to_string(),
+                    peer: peer.to_string(),
+                    nb_links: a.len(),
+                });
+                continue;
+            }
+            (Some(b), None) => {
+                changes.push(Change::PeerRemoved {
+                    router: router_name.to_string(),
+                    peer: peer.to_string(),
+                    nb_links: b.len(),
+                });
+                continue;
+            }
+            (None, None) => unreachable!(),
+        };
+
+        if links_before.len() != links_after.len() {
+            changes.push(Change::EcmpSizeChanged {
+                router: router_name.to_string(),
+                peer: peer.to_string(),
+                before: links_before.len(),
+                after: links_after.len(),
+            });
+        }
+
+        // Links are matched by label; repeated labels are matched in order
+        let mut unmatched: HashMap<&str, Vec<&Link>> = HashMap::new();
+        for link in links_before.iter().rev() {
+            unmatched.entry(link.label.as_str()).or_default().push(link);
+        }
+        let mut added = Vec::new();
+        for link in links_after.iter() {
+            match unmatched.get_mut(link.label.as_str()).and_then(|l| l.pop()) {
+                Some(old) => {
+                    if old.load.abs_diff(link.load) >= load_threshold {
+                        changes.push(Change::LoadChanged {
+                            router: router_name.to_string(),
+                            peer: peer.to_string(),
+                            label: link.label.to_string(),
+                            before: old.load,
+                            after: link.load,
+                        });
+                    }
+                }
+                None => added.push(link.label.to_string()),
+            }
+        }
+        let mut removed = unmatched
+            .into_values()
+            .flatten()
+            .map(|link| link.label.to_string())
+            .collect::<Vec<String>>();
+        if !added.is_empty() || !removed.is_empty() {
+            added.sort();
+            removed.sort();
+            changes.push(Change::LabelsChanged {
+                router: router_name.to_string(),
+                peer: peer.to_string(),
+                added,
+                removed,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::snapshot_from_value;
+
+    fn data(yaml: &str, t: i64) -> OvhData {
+        let value: serde_yaml::Value = serde_yaml::from_str(yaml).unwrap();
+        let snapshot = snapshot_from_value("test", &value).unwrap();
+        OvhData::from_snapshot(
+            snapshot,
+            chrono::DateTime::from_timestamp(t, 0).unwrap().naive_utc(),
+        )
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = data(
+            "a:\n  links:\n  - {label: '#1', load: 10, peer: b}\n  - {label: '#2', load: 10, peer: b}\n  - {label: '#1', load: 5, peer: c}\nb:\n  links: []\nc:\n  links: []\n",
+            0,
+        );
+        let after = data(
+            "a:\n  links:\n  - {label: '#1', load: 50, peer: b}\n  - {label: '#2', load: 20, peer: b}\n  - {label: '#3', load: 0, peer: b}\n  - {label: '#1', load: 5, peer: d}\nb:\n  links: []\nd:\n  links: []\n",
+            300,
+        );
+
+        let diff = before.diff(&after);
+        assert_eq!(
+            diff.changes,
+            vec![
+                Change::EcmpSizeChanged {
+                    router: "a".to_string(),
+                    peer: "b".to_string(),
+                    before: 2,
+                    after: 3
+                },
+                Change::LoadChanged {
+                    router: "a".to_string(),
+                    peer: "b".to_string(),
+                    label: "#1".to_string(),
+                    before: 10,
+                    after: 50
+                },
+                Change::LabelsChanged {
+                    router: "a".to_string(),
+                    peer: "b".to_string(),
+                    added: vec!["#3".to_string()],
+                    removed: vec![]
+                },
+                Change::PeerRemoved {
+                    router: "a".to_string(),
+                    peer: "c".to_string(),
+                    nb_links: 1
+                },
+                Change::PeerAdded {
+                    router: "a".to_string(),
+                    peer: "d".to_string(),
+                    nb_links: 1
+                },
+                Change::RouterRemoved {
+                    router: "c".to_string()
+                },
+                Change::RouterAdded {
+                    router: "d".to_string()
+                },
+            ]
+        );
+        assert!(before.diff(&before).is_empty());
+    }
+}
@@ -0,0 +1,72 @@
+use crate::input::is_archive;
+use crate::timestamp::{SkippedFile, TimestampExtractor};
+use crate::FileMetadata;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Snapshots stored at `path`: the file itself, or all the members of a tar archive.
+/// Files and members that are not snapshots are added to `skipped` with the reason why.
+pub fn get_path_files(
+    path: &Path,
+    extractor: &TimestampExtractor,
+    skipped: &mut Vec<SkippedFile>,
+) -> io::Result<Vec<FileMetadata>> {
+    if is_archive(path) {
+        let (files, archive_skipped) = FileMetadata::archive_to_file_metadata(path, extractor)?;
+        skipped.extend(archive_skipped);
+        return Ok(files);
+    }
+    let filename = path.file_name().and_then(|f| f.to_str());
+    if filename.is_some_and(TimestampExtractor::is_manifest) {
+        return Ok(Vec::new());
+    }
+    match FileMetadata::from_path(path, extractor) {
+        Ok(file) => Ok(vec![file]),
+        Err(reason) => {
+            skipped.push(SkippedFile {
+                path: path.to_string_lossy().to_string(),
+                reason,
+            });
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Snapshots of the directory `dirpath` sorted according to the timestamp, and the files left aside.
+/// The manifest of the directory, if any, is added to `extractor`.
+pub fn get_all_ovh_files_with(
+    dirpath: &str,
+    extractor: &TimestampExtractor,
+) -> Result<(Vec<FileMetadata>, Vec<SkippedFile>), String> {
+    let paths: Vec<PathBuf> = match fs::read_dir(dirpath) {
+        Ok(o) => o,
+        Err(e) => return Err(format!("Could not open directory: {:?}", e)),
+    }
+    .filter(|r| r.is_ok())
+    .map(|r| r.unwrap().path())
+    .filter(|p| !p.is_dir())
+    .collect();
+    let extractor = extractor
+        .for_directory(Path::new(dirpath))
+        .map_err(|e| format!("Could not read the timestamp manifest: {}", e))?;
+
+    let mut files: Vec<FileMetadata> = Vec::with_capacity(paths.len());
+    let mut skipped = Vec::new();
+    for pathbuf in paths.iter() {
+        match get_path_files(pathbuf, &extractor, &mut skipped) {
+            Ok(path_files) => files.extend(path_files),
+            Err(e) => return Err(format!("Could not read archive {:?}: {:?}", pathbuf, e)),
+        }
+    }
+
+    files.sort_by(|a, b| a.timestamp.partial_cmp(&b.timestamp).unwrap());
+
+    Ok((files, skipped))
+}
+
+/// Snapshots of the directory `dirpath`, sorted according to the timestamp.
+/// The files that are not snapshots are left out, see [`get_all_ovh_files_with`] to know which.
+pub fn get_all_ovh_files(dirpath: &str) -> Result<Vec<FileMetadata>, String> {
+    get_all_ovh_files_with(dirpath, &TimestampExtractor::default()).map(|(files, _)| files)
+}
@@ -0,0 +1,213 @@
+//! Transparent access to the snapshot files, whether they are plain, compressed
+//! (gzip, zstd, xz) or stored as members of a (possibly compressed) tar archive.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Compression of a file, detected from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    pub fn from_name(name: &str) -> Compression {
+        if name.ends_with(".gz") || name.ends_with(".tgz") {
+            Compression::Gzip
+        } else if name.ends_with(".zst") {
+            Compression::Zstd
+        } else if name.ends_with(".xz") || name.ends_with(".txz") {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Wraps `reader` with the matching decoder.
+    pub fn decoder<'a, R: Read + 'a>(&self, reader: R) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new(reader)),
+        })
+    }
+}
+
+/// Returns the file name without its compression extension,
+/// e.g., `europe_1650000000.yaml` for `europe_1650000000.yaml.gz`.
+pub fn strip_compression_extension(name: &str) -> &str {
+    [".gz", ".zst", ".xz"]
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .unwrap_or(name)
+}
+
+/// Whether the path is a tar archive, compressed or not.
+pub fn is_archive(path: &Path) -> bool {
+    match path.file_name().and_then(|f| f.to_str()) {
+        Some(name) => {
+            strip_compression_extension(name).ends_with(".tar")
+                || name.ends_with(".tgz")
+                || name.ends_with(".txz")
+        }
+        None => false,
+    }
+}
+
+/// Uncompressed copy of a compressed tar archive, deleted once no member refers to it.
+#[derive(Debug)]
+pub struct UnpackedArchive(tempfile::TempPath);
+
+impl UnpackedArchive {
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl PartialEq for UnpackedArchive {
+    fn eq(&self, other: &UnpackedArchive) -> bool {
+        self.path() == other.path()
+    }
+}
+
+impl Eq for UnpackedArchive {}
+
+/// A snapshot stored inside a tar archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveMember {
+    /// Path of the member inside the archive.
+    pub name: String,
+    /// Position of the member data in the uncompressed archive.
+    pub offset: u64,
+    pub size: u64,
+    /// Copy where `offset` points to, if the archive is compressed.
+    pub unpacked: Option<Arc<UnpackedArchive>>,
+}
+
+/// Opens a plain or compressed file for reading.
+pub fn open_file(path: &str) -> io::Result<Box<dyn Read>> {
+    let fd = BufReader::new(File::open(path)?);
+    Compression::from_name(path).decoder(fd)
+}
+
+/// Opens a member of a tar archive for reading, directly at its position.
+pub fn open_archive_member(archive: &str, member: &ArchiveMember) -> io::Result<Box<dyn Read>> {
+    let mut fd = match &member.unpacked {
+        Some(unpacked) => File::open(unpacked.path())?,
+        None => File::open(archive)?,
+    };
+    fd.seek(SeekFrom::Start(member.offset))?;
+    let mut data = Vec::with_capacity(member.size as usize);
+    fd.take(member.size).read_to_end(&mut data)?;
+    Compression::from_name(&member.name).decoder(Cursor::new(data))
+}
+
+/// Lists the regular files of a tar archive.
+/// A compressed archive is decompressed once in a temporary file, where its members are then
+/// read like in a plain archive, rather than decompressing it again for each of them.
+pub fn list_archive(archive: &str) -> io::Result<Vec<ArchiveMember>> {
+    let (path, unpacked) = match Compression::from_name(archive) {
+        Compression::None => (PathBuf::from(archive), None),
+        _ => {
+            let mut copy = tempfile::NamedTempFile::new()?;
+            let mut wrt = BufWriter::new(copy.as_file_mut());
+            io::copy(&mut open_file(archive)?, &mut wrt)?;
+            wrt.flush()?;
+            drop(wrt);
+            let unpacked = Arc::new(UnpackedArchive(copy.into_temp_path()));
+            (unpacked.path().to_path_buf(), Some(unpacked))
+        }
+    };
+    let mut tar = tar::Archive::new(BufReader::new(File::open(path)?));
+    let mut members = Vec::new();
+    for entry in tar.entries_with_seek()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = match entry.path()?.to_str() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        members.push(ArchiveMember {
+            name,
+            offset: entry.raw_file_position(),
+            size: entry.size(),
+            unpacked: unpacked.clone(),
+        });
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_archive_names() {
+        assert!(is_archive(Path::new("data/2022-03.tar")));
+        assert!(is_archive(Path::new("data/2022-03.tar.zst")));
+        assert!(is_archive(Path::new("data/2022-03.tgz")));
+        assert!(!is_archive(Path::new("data/europe_1650000000.yaml.gz")));
+        assert_eq!(
+            strip_compression_extension("europe_1650000000.yaml.zst"),
+            "europe_1650000000.yaml"
+        );
+    }
+
+    #[test]
+    fn test_gzip_decoder() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(b"a:\n  links: []\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut content = String::new();
+        Compression::from_name("europe_1650000000.yaml.gz")
+            .decoder(Cursor::new(compressed))
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "a:\n  links: []\n");
+    }
+
+    #[test]
+    fn test_compressed_archive() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in [("a.yaml", "a: 1\n"), ("b.yaml", "b: 2\n")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(&builder.into_inner().unwrap()).unwrap();
+        let path = std::env::temp_dir().join(format!("ovh-archive-{}.tar.gz", std::process::id()));
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let archive = path.to_str().unwrap();
+        let members = list_archive(archive).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(members.len(), 2);
+        // Read from the uncompressed copy, even once the archive is gone
+        let mut content = String::new();
+        open_archive_member(archive, &members[1])
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "b: 2\n");
+
+        let unpacked = members[0].unpacked.as_ref().unwrap().path().to_path_buf();
+        drop(members);
+        assert!(!unpacked.exists());
+    }
+}
@@ -0,0 +1,418 @@
+//! Model of an OVH weather map snapshot (routers, peers and links),
+//! shared by the analysis tools and the YAML readers.
+
+use chrono::prelude::NaiveDateTime;
+use serde::Serialize;
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::{cmp, collections::HashMap, path::Path};
+pub mod cache;
+pub mod dataset;
+pub mod diff;
+pub mod get_files;
+pub mod input;
+pub mod snapshot;
+pub mod timestamp;
+
+pub use cache::SnapshotCache;
+pub use dataset::{Dataset, MapConfig, OvhMap};
+pub use diff::{Change, SnapshotDiff};
+pub use input::{ArchiveMember, Compression, UnpackedArchive};
+pub use snapshot::{
+    read_snapshot, read_snapshot_from, write_snapshot, write_snapshot_file, LinkField, ParseError,
+    Snapshot, SnapshotFormat, SnapshotLink, SnapshotRouter,
+};
+pub use timestamp::{SkipReason, SkippedFile, TimestampExtractor, TimestampPattern};
+
+#[derive(Debug, Clone)]
+pub struct FileMetadata {
+    pub filepath: String,
+    pub timestamp: NaiveDateTime,
+    /// If set, the snapshot is this member of the tar archive `filepath`.
+    pub member: Option<ArchiveMember>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OvhNodeFilter {
+    All,
+    Ovh,
+    External,
+}
+
+impl FileMetadata {
+    /// Metadata of a plain or compressed snapshot file, e.g., `europe_1650000000.yaml.gz`,
+    /// with the default timestamp patterns.
+    pub fn path_to_file_metadata(pathbuf: &Path) -> Option<FileMetadata> {
+        FileMetadata::from_path(pathbuf, &TimestampExtractor::default()).ok()
+    }
+
+    /// Metadata of a plain or compressed snapshot file, or the reason why it is not one.
+    pub fn from_path(
+        pathbuf: &Path,
+        extractor: &TimestampExtractor,
+    ) -> Result<FileMetadata, SkipReason> {
+        let filepath = pathbuf.to_str().ok_or(SkipReason::NotUtf8)?;
+        let filename = pathbuf
+            .file_name()
+            .and_then(|f| f.to_str())
+            .ok_or(SkipReason::NotUtf8)?;
+
+        Ok(FileMetadata {
+            filepath: filepath.to_string(),
+            timestamp: extractor.extract(filename)?,
+            member: None,
+        })
+    }
+
+    /// Metadata of all the snapshots stored in a tar archive, and the members that are not snapshots.
+    /// The timestamp is taken from the name of each member.
+    pub fn archive_to_file_metadata(
+        pathbuf: &Path,
+        extractor: &TimestampExtractor,
+    ) -> io::Result<(Vec<FileMetadata>, Vec<SkippedFile>)> {
+        let archive = pathbuf
+            .to_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "non UTF-8 archive path"))?;
+        let mut files = Vec::new();
+        let mut skipped = Vec::new();
+        for member in input::list_archive(archive)? {
+            let path = format!("{}/{}", archive, member.name);
+            let timestamp = match Path::new(&member.name).file_name().and_then(|f| f.to_str()) {
+                Some(filename) if TimestampExtractor::is_manifest(filename) => continue,
+                Some(filename) => extractor.extract(filename),
+                None => Err(SkipReason::NotUtf8),
+            };
+            match timestamp {
+                Ok(timestamp) => files.push(FileMetadata {
+                    filepath: archive.to_string(),
+                    timestamp,
+                    member: Some(member),
+                }),
+                Err(reason) => skipped.push(SkippedFile { path, reason }),
+            }
+        }
+        Ok((files, skipped))
+    }
+
+    /// Human-readable location of the snapshot, `<archive>/<member>` for archived ones.
+    pub fn display_path(&self) -> String {
+        match &self.member {
+            Some(member) => format!("{}/{}", self.filepath, member.name),
+            None => self.filepath.to_string(),
+        }
+    }
+
+    /// Opens the snapshot for reading, decompressing it if needed.
+    pub fn open(&self) -> io::Result<Box<dyn Read>> {
+        match &self.member {
+            Some(member) => input::open_archive_member(&self.filepath, member),
+            None => input::open_file(&self.filepath),
+        }
+    }
+
+    /// Parses the snapshot, wherever it is stored.
+    pub fn parse(&self) -> Result<OvhData, ParseError> {
+        let path = self.display_path();
+        let reader = self.open().map_err(|source| ParseError::Io {
+            path: path.to_string(),
+            source,
+        })?;
+        read_snapshot_from(&path, reader)
+            .map(|snapshot| OvhData::from_snapshot(snapshot, self.timestamp))
+    }
+}
+
+/// Whether a router name is the one of an external peer, written in uppercase, e.g., `AMS-IX`.
+pub fn is_peer_from_name(name: &str) -> bool {
+    let str_split: Vec<&str> = name.split('#').collect();
+    str_split[0].to_uppercase() == str_split[0]
+}
+
+/// Point of presence of an OVH router, the part of its name before the first `-`,
+/// e.g., `ams` for `ams-1-n7`. `None` for the external peers.
+pub fn pop_from_name(name: &str) -> Option<&str> {
+    match is_peer_from_name(name) {
+        true => None,
+        false => name.split('-').next(),
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Link {
+    pub label: String,
+    pub load: u32,
+}
+
+#[derive(Debug)]
+pub struct Router {
+    pub name: String,
+    pub peers: HashMap<String, Vec<Link>>,
+}
+
+impl Router {
+    pub fn is_external(&self) -> bool {
+        is_peer_from_name(&self.name)
+    }
+
+    pub fn pop(&self) -> Option<&str> {
+        pop_from_name(&self.name)
+    }
+
+    pub fn has_external(&self) -> bool {
+        self.peers
+            .keys()
+            .any(|peer_name| is_peer_from_name(peer_name))
+    }
+
+    pub fn get_external_links(&self) -> Option<Vec<&Vec<Link>>> {
+        if self.is_external() {
+            return None;
+        }
+        Some(
+            self.peers
+                .iter()
+                .filter(|(peer_name, _)| is_peer_from_name(peer_name))
+                .map(|(_, links)| links)
+                .collect::<Vec<&Vec<Link>>>(),
+        )
+    }
+
+    pub fn get_nb_links(&self) -> i32 {
+        self.peers.values().map(|v| v.len() as i32).sum::<i32>()
+    }
+
+    pub fn get_links_load_with(&self, other: &str) -> Option<Vec<u32>> {
+        self.peers
+            .get(other)
+            .map(|peer| peer.iter().map(|link| link.load).collect())
+    }
+}
+
+#[derive(Debug)]
+pub struct OvhData {
+    pub timestamp: NaiveDateTime,
+    pub data: HashMap<String, Router>,
+}
+
+impl OvhData {
+    /// Builds the router graph from a validated snapshot.
+    pub fn from_snapshot(snapshot: Snapshot, timestamp: NaiveDateTime) -> OvhData {
+        let data = snapshot
+            .into_iter()
+            .map(|(router_name, router)| {
+                let mut r = Router {
+                    name: router_name.to_owned(),
+                    peers: HashMap::new(),
+                };
+                for link in router.links {
+                    r.peers.entry(link.peer).or_default().push(Link {
+                        label: link.label,
+                        load: link.load,
+                    });
+                }
+                (router_name, r)
+            })
+            .collect();
+
+        OvhData { timestamp, data }
+    }
+
+    /// Converts back to the on-disk schema. Peers are sorted by name,
+    /// and the parallel links towards a peer keep their order.
+    pub fn to_snapshot(&self) -> Snapshot {
+        self.data
+            .iter()
+            .map(|(router_name, router)| {
+                let mut peers = router.peers.iter().collect::<Vec<(&String, &Vec<Link>)>>();
+                peers.sort_by_key(|(peer_name, _)| *peer_name);
+                let links = peers
+                    .into_iter()
+                    .flat_map(|(peer_name, links)| {
+                        links.iter().map(move |link| SnapshotLink {
+                            label: link.label.to_string(),
+                            load: link.load,
+                            peer: peer_name.to_string(),
+                        })
+                    })
+                    .collect();
+                (router_name.to_string(), SnapshotRouter { links })
+            })
+            .collect()
+    }
+
+    /// Writes the snapshot in the `{router: {links: [{label, load, peer}]}}` format
+    /// read by `parse_yaml`.
+    pub fn write<W: Write>(&self, wrt: W, format: SnapshotFormat) -> Result<(), Box<dyn Error>> {
+        write_snapshot(&self.to_snapshot(), wrt, format)
+    }
+
+    /// Writes the snapshot to `filepath`, as JSON if its extension is `.json` and YAML otherwise.
+    pub fn write_to_file(&self, filepath: &str) -> Result<(), Box<dyn Error>> {
+        write_snapshot_file(&self.to_snapshot(), filepath)
+    }
+
+    pub fn get_peering_routers(&self) -> Vec<&Router> {
+        self.data
+            .iter()
+            .filter(|(_, router)| router.is_external())
+            .map(|(_, router)| router)
+            .collect::<Vec<&Router>>()
+    }
+
+    pub fn get_internal_routers(&self) -> Vec<&Router> {
+        self.data
+            .iter()
+            .filter(|(_, router)| !router.is_external())
+            .map(|(_, router)| router)
+            .collect::<Vec<&Router>>()
+    }
+
+    pub fn get_border_routers(&self) -> Vec<&Router> {
+        self.data
+            .iter()
+            .filter(|(_, router)| router.has_external())
+            .map(|(_, router)| router)
+            .collect()
+    }
+
+    pub fn get_router_links_load_with(
+        &self,
+        router_name: &str,
+        peer_name: &str,
+    ) -> Option<Vec<u32>> {
+        match self.data.get(router_name) {
+            Some(router) => router.get_links_load_with(peer_name),
+            None => None,
+        }
+    }
+
+    pub fn get_nb_nodes(&self, ovh_nodes: OvhNodeFilter) -> i32 {
+        match ovh_nodes {
+            OvhNodeFilter::Ovh => self.get_internal_routers().len() as i32,
+            OvhNodeFilter::External => self.get_peering_routers().len() as i32,
+            OvhNodeFilter::All => self.data.len() as i32,
+        }
+    }
+
+    pub fn get_nb_links(&self, ovh_nodes: OvhNodeFilter) -> i32 {
+        (self
+            .data
+            .values()
+            .filter(|router| match ovh_nodes {
+                OvhNodeFilter::Ovh => !router.is_external(),
+                OvhNodeFilter::External => router.is_external(),
+                OvhNodeFilter::All => true,
+            })
+            .map(|router| {
+                router
+                    .peers
+                    .iter()
+                    .filter(|(peer_name, _)| match ovh_nodes {
+                        OvhNodeFilter::Ovh => !is_peer_from_name(peer_name),
+                        _ => true,
+                    })
+                    .map(|(_, peering)| peering.len())
+                    .sum::<usize>()
+            })
+            .sum::<usize>()
+            / match ovh_nodes {
+                OvhNodeFilter::External => 1, //
+                _ => 2,
+            }) as i32
+    }
+
+    /// For each router in the network, computes the ECMP imbalance,
+    /// i.e., the difference in load between all links with the same source and destination.
+    /// We do *not* take into account the loads of:
+    ///     - 0%: no traffic, unused link,
+    ///     - 1%: assume that it represents only traffic control
+    pub fn get_ecmp_imbalance(&self, ovh_nodes: OvhNodeFilter) -> Vec<i8> {
+        // I first made it using functional programming, but it is way cleaner like this
+        let mut output: Vec<i8> = Vec::with_capacity(self.data.len()); // Random initialization
+        for router in self.data.values().filter(|&r| match ovh_nodes {
+            OvhNodeFilter::Ovh => !r.is_external(),
+            OvhNodeFilter::External => r.is_external(),
+            OvhNodeFilter::All => true,
+        }) {
+            for (_, peer_links) in router
+                .peers
+                .iter()
+                .filter(|(peer_name, _)| match ovh_nodes {
+                    OvhNodeFilter::Ovh => &&peer_name.to_uppercase() != peer_name,
+                    _ => true,
+                })
+            {
+                let filtered_links: Vec<&Link> =
+                    peer_links.iter().filter(|&link| link.load > 1).collect();
+                if filtered_links.len() > 1 {
+                    let min_load = filtered_links
+                        .iter()
+                        .fold(filtered_links[0].load, |a, b| cmp::min(a, b.load))
+                        as i8;
+                    let max_load = filtered_links
+                        .iter()
+                        .fold(filtered_links[0].load, |a, b| cmp::max(a, b.load))
+                        as i8;
+                    output.push(max_load - min_load);
+                }
+            }
+        }
+
+        output
+    }
+
+    pub fn get_nb_ecmp_links(&self, ovh_nodes: OvhNodeFilter) -> Vec<i8> {
+        let mut output: Vec<i8> = Vec::with_capacity(self.data.len());
+        for router in self.data.values().filter(|&r| match ovh_nodes {
+            OvhNodeFilter::Ovh => !r.is_external(),
+            OvhNodeFilter::External => r.is_external(),
+            OvhNodeFilter::All => true,
+        }) {
+            for (_, peer_links) in router
+                .peers
+                .iter()
+                .filter(|(peer_name, _)| match ovh_nodes {
+                    OvhNodeFilter::Ovh => !is_peer_from_name(peer_name),
+                    _ => true,
+                })
+            {
+                if peer_links.len() > 1 {
+                    output.push(peer_links.len() as i8);
+                }
+            }
+        }
+
+        output
+    }
+
+    pub fn get_link_loads(&self, ovh_nodes: OvhNodeFilter) -> Vec<i8> {
+        let mut output: Vec<i8> = Vec::with_capacity(self.data.len());
+        for router in self.data.values().filter(|&r| match ovh_nodes {
+            OvhNodeFilter::Ovh => !r.is_external(),
+            OvhNodeFilter::External => r.is_external(),
+            OvhNodeFilter::All => true,
+        }) {
+            for (_, peer_links) in router
+                .peers
+                .iter()
+                .filter(|(peer_name, _)| match ovh_nodes {
+                    OvhNodeFilter::Ovh => is_peer_from_name(peer_name),
+                    _ => true,
+                })
+            {
+                peer_links
+                    .iter()
+                    .filter(|&link| link.load > 1)
+                    .for_each(|link| output.push(link.load as i8));
+            }
+        }
+
+        output
+    }
+}
+
+/// Parses a YAML snapshot (possibly compressed) into an `OvhData`.
+/// On failure, the error describes which file, router, link and field was malformed.
+pub fn parse_yaml(filepath: &str, timestamp: NaiveDateTime) -> Result<OvhData, ParseError> {
+    read_snapshot(filepath).map(|snapshot| OvhData::from_snapshot(snapshot, timestamp))
+}
@@ -0,0 +1,456 @@
+//! On-disk schema of a weather map snapshot, as produced by `weathermap_parse.py`:
+//!
+//! ```yaml
+//! router-name:
+//!   links:
+//!     - label: "#1"
+//!       load: 42
+//!       peer: other-router
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::input::open_file;
+
+/// One directed link of a router, as stored in the YAML snapshot.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SnapshotLink {
+    pub label: String,
+    pub load: u32,
+    pub peer: String,
+}
+
+/// All the links leaving a router.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct SnapshotRouter {
+    pub links: Vec<SnapshotLink>,
+}
+
+/// A full snapshot, indexed by router name.
+pub type Snapshot = BTreeMap<String, SnapshotRouter>;
+
+/// Field of a link in the snapshot schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkField {
+    Label,
+    Load,
+    Peer,
+}
+
+impl LinkField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkField::Label => "label",
+            LinkField::Load => "load",
+            LinkField::Peer => "peer",
+        }
+    }
+}
+
+impl fmt::Display for LinkField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Reason why a snapshot file could not be turned into an `OvhData`.
+/// Every variant carries the path of the faulty file.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The file could not be opened or read.
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    /// The file is not valid YAML.
+    Yaml {
+        path: String,
+        source: serde_yaml::Error,
+    },
+    /// The document is not a mapping of router names.
+    NotAMapping { path: String },
+    /// A router key is not a string.
+    InvalidRouterName { path: String, key: String },
+    /// A router entry is not a `{links: [...]}` mapping.
+    InvalidRouter {
+        path: String,
+        router: String,
+        reason: String,
+    },
+    /// A link of `router` at position `link_index` lacks `field`.
+    MissingField {
+        path: String,
+        router: String,
+        link_index: usize,
+        field: LinkField,
+    },
+    /// A link of `router` at position `link_index` has an unexpected value for `field`.
+    InvalidField {
+        path: String,
+        router: String,
+        link_index: usize,
+        field: LinkField,
+        value: String,
+    },
+}
+
+impl ParseError {
+    /// Path of the file that triggered the error.
+    pub fn path(&self) -> &str {
+        match self {
+            ParseError::Io { path, .. }
+            | ParseError::Yaml { path, .. }
+            | ParseError::NotAMapping { path }
+            | ParseError::InvalidRouterName { path, .. }
+            | ParseError::InvalidRouter { path, .. }
+            | ParseError::MissingField { path, .. }
+            | ParseError::InvalidField { path, .. } => path,
+        }
+    }
+
+    /// Name of the router involved in the error, if any.
+    pub fn router(&self) -> Option<&str> {
+        match self {
+            ParseError::InvalidRouter { router, .. }
+            | ParseError::MissingField { router, .. }
+            | ParseError::InvalidField { router, .. } => Some(router),
+            _ => None,
+        }
+    }
+
+    /// Short machine-readable identifier of the kind of error.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ParseError::Io { .. } => "io",
+            ParseError::Yaml { .. } => "yaml",
+            ParseError::NotAMapping { .. } => "not-a-mapping",
+            ParseError::InvalidRouterName { .. } => "invalid-router-name",
+            ParseError::InvalidRouter { .. } => "invalid-router",
+            ParseError::MissingField { .. } => "missing-field",
+            ParseError::InvalidField { .. } => "invalid-field",
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io { path, source } => write!(f, "{}: cannot read file: {}", path, source),
+            ParseError::Yaml { path, source } => write!(f, "{}: invalid YAML: {}", path, source),
+            ParseError::NotAMapping { path } => {
+                write!(f, "{}: the document is not a mapping of routers", path)
+            }
+            ParseError::InvalidRouterName { path, key } => {
+                write!(f, "{}: router name is not a string: {}", path, key)
+            }
+            ParseError::InvalidRouter {
+                path,
+                router,
+                reason,
+            } => write!(f, "{}: router {}: {}", path, router, reason),
+            ParseError::MissingField {
+                path,
+                router,
+                link_index,
+                field,
+            } => write!(
+                f,
+                "{}: router {}, link {}: missing field `{}`",
+                path, router, link_index, field
+            ),
+            ParseError::InvalidField {
+                path,
+                router,
+                link_index,
+                field,
+                value,
+            } => write!(
+                f,
+                "{}: router {}, link {}: invalid value for `{}`: {}",
+                path, router, link_index, field, value
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io { source, .. } => Some(source),
+            ParseError::Yaml { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match serde_yaml::to_string(value) {
+        Ok(s) => s.trim_start_matches("---").trim().to_string(),
+        Err(_) => format!("{:?}", value),
+    }
+}
+
+fn link_field<'a>(
+    path: &str,
+    router: &str,
+    link_index: usize,
+    link: &'a Mapping,
+    field: LinkField,
+) -> Result<&'a Value, ParseError> {
+    link.get(&Value::String(field.as_str().to_string()))
+        .ok_or_else(|| ParseError::MissingField {
+            path: path.to_string(),
+            router: router.to_string(),
+            link_index,
+            field,
+        })
+}
+
+fn parse_link(
+    path: &str,
+    router: &str,
+    link_index: usize,
+    link: &Value,
+) -> Result<SnapshotLink, ParseError> {
+    let invalid = |field: LinkField, value: &Value| ParseError::InvalidField {
+        path: path.to_string(),
+        router: router.to_string(),
+        link_index,
+        field,
+        value: value_to_string(value),
+    };
+    let link = link.as_mapping().ok_or_else(|| ParseError::InvalidRouter {
+        path: path.to_string(),
+        router: router.to_string(),
+        reason: format!("link {} is not a mapping", link_index),
+    })?;
+
+    let label = link_field(path, router, link_index, link, LinkField::Label)?;
+    let label = label
+        .as_str()
+        .ok_or_else(|| invalid(LinkField::Label, label))?;
+
+    let load = link_field(path, router, link_index, link, LinkField::Load)?;
+    let load = load
+        .as_u64()
+        .and_then(|l| u32::try_from(l).ok())
+        .ok_or_else(|| invalid(LinkField::Load, load))?;
+
+    let peer = link_field(path, router, link_index, link, LinkField::Peer)?;
+    let peer = peer
+        .as_str()
+        .ok_or_else(|| invalid(LinkField::Peer, peer))?;
+
+    Ok(SnapshotLink {
+        label: label.to_string(),
+        load,
+        peer: peer.to_string(),
+    })
+}
+
+/// Validates a YAML document against the snapshot schema.
+/// `path` is only used to report errors.
+pub fn snapshot_from_value(path: &str, document: &Value) -> Result<Snapshot, ParseError> {
+    let routers = document
+        .as_mapping()
+        .ok_or_else(|| ParseError::NotAMapping {
+            path: path.to_string(),
+        })?;
+
+    let mut snapshot = Snapshot::new();
+    for (key, value) in routers {
+        let router = key.as_str().ok_or_else(|| ParseError::InvalidRouterName {
+            path: path.to_string(),
+            key: value_to_string(key),
+        })?;
+        let invalid_router = |reason: &str| ParseError::InvalidRouter {
+            path: path.to_string(),
+            router: router.to_string(),
+            reason: reason.to_string(),
+        };
+        let links = value
+            .as_mapping()
+            .ok_or_else(|| invalid_router("not a mapping"))?
+            .get(&Value::String("links".to_string()))
+            .ok_or_else(|| invalid_router("missing `links`"))?
+            .as_sequence()
+            .ok_or_else(|| invalid_router("`links` is not a sequence"))?;
+
+        let links = links
+            .iter()
+            .enumerate()
+            .map(|(idx, link)| parse_link(path, router, idx, link))
+            .collect::<Result<Vec<SnapshotLink>, ParseError>>()?;
+        snapshot.insert(router.to_string(), SnapshotRouter { links });
+    }
+
+    Ok(snapshot)
+}
+
+/// Reads and validates a YAML snapshot from any reader.
+/// `path` is only used to report errors.
+pub fn read_snapshot_from<R: Read>(path: &str, reader: R) -> Result<Snapshot, ParseError> {
+    let document: Value = serde_yaml::from_reader(reader).map_err(|source| ParseError::Yaml {
+        path: path.to_string(),
+        source,
+    })?;
+    snapshot_from_value(path, &document)
+}
+
+/// Reads and validates a YAML snapshot file, possibly compressed.
+pub fn read_snapshot(filepath: &str) -> Result<Snapshot, ParseError> {
+    let reader = open_file(filepath).map_err(|source| ParseError::Io {
+        path: filepath.to_string(),
+        source,
+    })?;
+    read_snapshot_from(filepath, reader)
+}
+
+/// Serialization format of a snapshot file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    Yaml,
+    Json,
+}
+
+impl SnapshotFormat {
+    /// Guesses the format from the file extension. Anything but `.json` is YAML.
+    pub fn from_path(path: &Path) -> SnapshotFormat {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => SnapshotFormat::Json,
+            _ => SnapshotFormat::Yaml,
+        }
+    }
+}
+
+/// Writes a snapshot in the given format.
+/// Routers are sorted by name, so the same snapshot always gives the same bytes.
+pub fn write_snapshot<W: Write>(
+    snapshot: &Snapshot,
+    mut wrt: W,
+    format: SnapshotFormat,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        SnapshotFormat::Yaml => serde_yaml::to_writer(&mut wrt, snapshot)?,
+        SnapshotFormat::Json => {
+            serde_json::to_writer_pretty(&mut wrt, snapshot)?;
+            writeln!(wrt)?;
+        }
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+/// Writes a snapshot to `filepath`, in the format given by its extension.
+pub fn write_snapshot_file(snapshot: &Snapshot, filepath: &str) -> Result<(), Box<dyn Error>> {
+    let fd = std::fs::File::create(filepath)?;
+    write_snapshot(
+        snapshot,
+        std::io::BufWriter::new(fd),
+        SnapshotFormat::from_path(Path::new(filepath)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(document: &str) -> Result<Snapshot, ParseError> {
+        snapshot_from_value("test.yaml", &serde_yaml::from_str(document).unwrap())
+    }
+
+    #[test]
+    fn test_valid_snapshot() {
+        let snapshot = parse(
+            "a:\n  links:\n  - {label: '#1', load: 12, peer: b}\nb:\n  links:\n  - {label: '#1', load: 3, peer: a}\n",
+        )
+        .unwrap();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(
+            snapshot["a"].links[0],
+            SnapshotLink {
+                label: "#1".to_string(),
+                load: 12,
+                peer: "b".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let snapshot = parse(
+            "b:\n  links:\n  - {label: '#2', load: 3, peer: a}\na:\n  links:\n  - {label: '#1', load: 12, peer: b}\n  - {label: '#3', load: 0, peer: b}\n",
+        )
+        .unwrap();
+
+        for format in [SnapshotFormat::Yaml, SnapshotFormat::Json] {
+            let mut first = Vec::new();
+            write_snapshot(&snapshot, &mut first, format).unwrap();
+            let text = String::from_utf8(first.clone()).unwrap();
+            assert!(text.find("a").unwrap() < text.find("b").unwrap());
+
+            let read_back = parse(&text).unwrap();
+            assert_eq!(read_back, snapshot);
+
+            let mut second = Vec::new();
+            write_snapshot(&read_back, &mut second, format).unwrap();
+            assert_eq!(first, second);
+        }
+    }
+
+    #[test]
+    fn test_missing_field() {
+        let err = parse(
+            "a:\n  links:\n  - {label: '#1', load: 12, peer: b}\n  - {label: '#2', peer: b}\n",
+        )
+        .unwrap_err();
+        match err {
+            ParseError::MissingField {
+                router,
+                link_index,
+                field,
+                ..
+            } => {
+                assert_eq!(router, "a");
+                assert_eq!(link_index, 1);
+                assert_eq!(field, LinkField::Load);
+            }
+            e => panic!("Unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_invalid_field() {
+        let err = parse("a:\n  links:\n  - {label: '#1', load: 12, peer: [b]}\n").unwrap_err();
+        assert_eq!(err.kind(), "invalid-field");
+        assert_eq!(err.router(), Some("a"));
+        assert_eq!(err.path(), "test.yaml");
+
+        let err = parse("a:\n  links:\n  - {label: '#1', load: -3, peer: b}\n").unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::InvalidField {
+                field: LinkField::Load,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_invalid_structure() {
+        assert!(matches!(
+            parse("- a\n- b\n").unwrap_err(),
+            ParseError::NotAMapping { .. }
+        ));
+        assert!(matches!(
+            parse("a:\n  peers: []\n").unwrap_err(),
+            ParseError::InvalidRouter { .. }
+        ));
+    }
+}
@@ -0,0 +1,316 @@
+//! Extraction of the snapshot timestamp from its file name.
+//!
+//! By default, snapshots are named `<prefix>_<unix timestamp>.yaml`, possibly compressed,
+//! and the prefix may itself contain underscores. ISO-8601 names such as
+//! `europe_2022-04-15T05:20:00Z.yaml` are accepted too, and other layouts can be described
+//! with chrono format strings. When the names carry no usable timestamp, a sidecar
+//! manifest (`timestamps.yaml`) next to the files gives it explicitly:
+//!
+//! ```yaml
+//! europe-latest.yaml: 1650000000
+//! europe-noon.yaml: 2022-04-15T12:00:00Z
+//! ```
+
+use crate::input::strip_compression_extension;
+use chrono::prelude::{DateTime, NaiveDateTime};
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Name of the sidecar manifest looked up in each directory.
+pub const MANIFEST_NAME: &str = "timestamps.yaml";
+
+/// Extensions of the snapshot files, once the compression extension is removed.
+const SNAPSHOT_EXTENSIONS: &[&str] = &[".yaml", ".yml", ".json"];
+
+/// ISO-8601 layouts accepted in file names, where `:` is often replaced by `-`.
+const ISO_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H-%M-%S",
+    "%Y-%m-%dT%H%M%S",
+    "%Y%m%dT%H%M%S",
+];
+
+/// How a timestamp is written in a file name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimestampPattern {
+    /// Seconds since the epoch, e.g., `europe_1650000000.yaml`.
+    Unix,
+    /// ISO-8601 date and time, in UTC unless an offset is given, e.g., `europe_2022-04-15T05:20:00Z.yaml`.
+    Iso8601,
+    /// A chrono format string, in UTC, e.g., `%Y-%m-%d_%H-%M`.
+    Format(String),
+}
+
+impl TimestampPattern {
+    fn parse(&self, value: &str) -> Option<NaiveDateTime> {
+        match self {
+            TimestampPattern::Unix => {
+                if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                Some(DateTime::from_timestamp(value.parse().ok()?, 0)?.naive_utc())
+            }
+            TimestampPattern::Iso8601 => parse_iso8601(value),
+            TimestampPattern::Format(format) => NaiveDateTime::parse_from_str(value, format).ok(),
+        }
+    }
+}
+
+impl FromStr for TimestampPattern {
+    type Err = String;
+
+    /// `unix`, `iso8601`, or any other string taken as a chrono format.
+    fn from_str(s: &str) -> Result<TimestampPattern, String> {
+        match s {
+            "" => Err("Empty timestamp pattern".to_string()),
+            "unix" => Ok(TimestampPattern::Unix),
+            "iso8601" | "iso" => Ok(TimestampPattern::Iso8601),
+            format if format.contains('%') => Ok(TimestampPattern::Format(format.to_string())),
+            _ => Err(format!("Unknown timestamp pattern: {}", s)),
+        }
+    }
+}
+
+fn parse_iso8601(value: &str) -> Option<NaiveDateTime> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Some(t.naive_utc());
+    }
+    let value = value.strip_suffix('Z').unwrap_or(value);
+    ISO_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+}
+
+/// Why a file found while listing the snapshots was not kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The file name is not valid UTF-8.
+    NotUtf8,
+    /// The extension is not the one of a snapshot.
+    NotASnapshot,
+    /// No pattern matches the file name, and it is absent from the manifest.
+    NoTimestamp,
+    /// The timestamp is well-formed but out of the representable range.
+    TimestampOutOfRange(String),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::NotUtf8 => write!(f, "file name is not valid UTF-8"),
+            SkipReason::NotASnapshot => write!(
+                f,
+                "not a snapshot (expected one of the extensions {})",
+                SNAPSHOT_EXTENSIONS.join(", ")
+            ),
+            SkipReason::NoTimestamp => write!(
+                f,
+                "no timestamp in the file name and no entry in {}",
+                MANIFEST_NAME
+            ),
+            SkipReason::TimestampOutOfRange(value) => {
+                write!(f, "timestamp {} is out of range", value)
+            }
+        }
+    }
+}
+
+/// A file left out of a listing, with the reason why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: SkipReason,
+}
+
+impl fmt::Display for SkippedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.reason)
+    }
+}
+
+/// Finds the timestamp of a snapshot from its name, trying the manifest first
+/// and then each pattern in order.
+#[derive(Debug, Clone)]
+pub struct TimestampExtractor {
+    patterns: Vec<TimestampPattern>,
+    manifest: HashMap<String, NaiveDateTime>,
+}
+
+impl Default for TimestampExtractor {
+    fn default() -> TimestampExtractor {
+        TimestampExtractor::new(vec![TimestampPattern::Unix, TimestampPattern::Iso8601])
+    }
+}
+
+impl TimestampExtractor {
+    pub fn new(patterns: Vec<TimestampPattern>) -> TimestampExtractor {
+        TimestampExtractor {
+            patterns,
+            manifest: HashMap::new(),
+        }
+    }
+
+    /// Adds the entries of a manifest file, mapping file names to a unix or ISO-8601 timestamp.
+    pub fn with_manifest(mut self, path: &Path) -> io::Result<TimestampExtractor> {
+        let invalid = |msg: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), msg),
+            )
+        };
+        let value: Value =
+            serde_yaml::from_reader(fs::File::open(path)?).map_err(|e| invalid(e.to_string()))?;
+        let mapping = match value {
+            Value::Mapping(mapping) => mapping,
+            Value::Null => return Ok(self),
+            _ => return Err(invalid("not a mapping of file names".to_string())),
+        };
+        for (name, timestamp) in mapping {
+            let name = match name {
+                Value::String(name) => name,
+                other => return Err(invalid(format!("invalid file name {:?}", other))),
+            };
+            let parsed = match &timestamp {
+                Value::Number(n) => n
+                    .as_i64()
+                    .and_then(|t| DateTime::from_timestamp(t, 0))
+                    .map(|t| t.naive_utc()),
+                Value::String(s) => parse_iso8601(s),
+                _ => None,
+            };
+            match parsed {
+                Some(t) => self.manifest.insert(name, t),
+                None => {
+                    return Err(invalid(format!(
+                        "invalid timestamp {:?} for {}",
+                        timestamp, name
+                    )))
+                }
+            };
+        }
+        Ok(self)
+    }
+
+    /// The extractor to use for the files of `dir`: this one, plus the manifest of `dir` if any.
+    pub fn for_directory(&self, dir: &Path) -> io::Result<TimestampExtractor> {
+        let manifest = dir.join(MANIFEST_NAME);
+        if manifest.is_file() {
+            self.clone().with_manifest(&manifest)
+        } else {
+            Ok(self.clone())
+        }
+    }
+
+    /// Whether `filename` is a manifest rather than a snapshot.
+    pub fn is_manifest(filename: &str) -> bool {
+        filename == MANIFEST_NAME
+    }
+
+    /// Timestamp of the snapshot named `filename` (without its directory).
+    pub fn extract(&self, filename: &str) -> Result<NaiveDateTime, SkipReason> {
+        let name = strip_compression_extension(filename);
+        if let Some(&t) = self
+            .manifest
+            .get(filename)
+            .or_else(|| self.manifest.get(name))
+        {
+            return Ok(t);
+        }
+
+        let stem = SNAPSHOT_EXTENSIONS
+            .iter()
+            .find_map(|ext| name.strip_suffix(ext))
+            .ok_or(SkipReason::NotASnapshot)?;
+
+        // The timestamp ends the name, after the prefix and an underscore.
+        // Shortest candidates first, so that underscores in the prefix are left aside.
+        let candidates = stem
+            .match_indices('_')
+            .map(|(idx, _)| &stem[idx + 1..])
+            .rev()
+            .chain(std::iter::once(stem));
+        for candidate in candidates {
+            for pattern in self.patterns.iter() {
+                if let Some(t) = pattern.parse(candidate) {
+                    return Ok(t);
+                }
+            }
+            if self.patterns.contains(&TimestampPattern::Unix)
+                && !candidate.is_empty()
+                && candidate.bytes().all(|b| b.is_ascii_digit())
+            {
+                return Err(SkipReason::TimestampOutOfRange(candidate.to_string()));
+            }
+        }
+        Err(SkipReason::NoTimestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unix(t: i64) -> NaiveDateTime {
+        DateTime::from_timestamp(t, 0).unwrap().naive_utc()
+    }
+
+    #[test]
+    fn test_default_patterns() {
+        let extractor = TimestampExtractor::default();
+        let t = Ok(unix(1650000000));
+        assert_eq!(extractor.extract("europe_1650000000.yaml"), t);
+        assert_eq!(extractor.extract("data_world_1650000000.yaml.zst"), t);
+        assert_eq!(extractor.extract("1650000000.json"), t);
+        assert_eq!(extractor.extract("europe_2022-04-15T05:20:00Z.yaml"), t);
+        assert_eq!(extractor.extract("europe_2022-04-15T05-20-00.yaml.gz"), t);
+        assert_eq!(
+            extractor.extract("europe.yaml"),
+            Err(SkipReason::NoTimestamp)
+        );
+        assert_eq!(extractor.extract("README"), Err(SkipReason::NotASnapshot));
+        assert_eq!(
+            extractor.extract("europe_99999999999999999999.yaml"),
+            Err(SkipReason::TimestampOutOfRange(
+                "99999999999999999999".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_format_and_manifest() {
+        let dir = std::env::temp_dir().join(format!("ovh-model-timestamp-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(MANIFEST_NAME),
+            "europe-latest.yaml: 1650000000\neurope-noon.yaml: 2022-04-15T12:00:00Z\n",
+        )
+        .unwrap();
+
+        let extractor = TimestampExtractor::new(vec!["%Y-%m-%d_%H-%M".parse().unwrap()])
+            .for_directory(&dir)
+            .unwrap();
+        assert_eq!(
+            extractor.extract("europe_2022-04-15_05-20.yaml"),
+            Ok(unix(1650000000))
+        );
+        assert_eq!(
+            extractor.extract("europe-latest.yaml.gz"),
+            Ok(unix(1650000000))
+        );
+        assert_eq!(
+            extractor.extract("europe-noon.yaml"),
+            Ok(unix(1650000000 + 6 * 3600 + 40 * 60))
+        );
+        assert_eq!(
+            extractor.extract("europe_1650000000.yaml"),
+            Err(SkipReason::NoTimestamp)
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
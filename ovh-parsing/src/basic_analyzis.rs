@@ -15,7 +15,7 @@ pub fn _nb_router_evolution(
     let serialized = res
         .iter()
         .zip(files)
-        .map(|(&value, &file)| (file.timestamp.timestamp(), value))
+        .map(|(&value, &file)| (file.timestamp.and_utc().timestamp(), value))
         .collect::<Vec<(i64, usize)>>();
 
     write_in_csv(serialized, output_csv)
@@ -46,7 +46,7 @@ pub fn _nb_links_evolution(
     let serialized = res
         .iter()
         .zip(files)
-        .map(|(&value, &file)| (file.timestamp.timestamp(), value))
+        .map(|(&value, &file)| (file.timestamp.and_utc().timestamp(), value))
         .collect::<Vec<(i64, usize)>>();
 
     write_in_csv(serialized, output_csv)
@@ -62,8 +62,8 @@ pub fn _node_degree_evolution(
         .map(|one_timestamp| {
             one_timestamp
                 .data
-                .iter()
-                .map(|(_, router)| router.peers.len())
+                .values()
+                .map(|router| router.peers.len())
                 .collect::<Vec<usize>>()
         })
         .collect::<Vec<Vec<usize>>>();
@@ -71,7 +71,7 @@ pub fn _node_degree_evolution(
     let _serialized = res
         .iter()
         .zip(files)
-        .map(|(v, &file)| (file.timestamp.timestamp(), v))
+        .map(|(v, &file)| (file.timestamp.and_utc().timestamp(), v))
         .collect::<Vec<(i64, &Vec<usize>)>>();
 
     // TODO: for now impossible to parse in CSV because the number of columns vary
@@ -81,7 +81,7 @@ pub fn _node_degree_evolution(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::NaiveDateTime;
+    use chrono::DateTime;
     use ovh_parsing::{Link, Router};
     use std::collections::HashMap;
 
@@ -212,7 +212,7 @@ mod tests {
 
         OvhData {
             data: square,
-            timestamp: NaiveDateTime::from_timestamp(1, 0),
+            timestamp: DateTime::from_timestamp(1, 0).unwrap().naive_utc(),
         }
     }
 }
@@ -0,0 +1,66 @@
+use clap::Parser;
+use ovh_parsing::{
+    get_files::get_all_ovh_files_with, load_store::LoadStore, stream::SnapshotStream,
+    SnapshotCache, TimestampExtractor,
+};
+
+/// Builds the columnar store of the link loads from a directory of snapshots
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Path to the directory containing the data
+    #[clap(short, long, value_parser)]
+    dir: String,
+    /// Path of the output store
+    #[clap(short, long, value_parser)]
+    output: String,
+    /// Directory of the binary snapshot cache
+    #[clap(long, value_parser)]
+    cache_dir: Option<String>,
+    /// Number of threads to parse the YAML files
+    #[clap(short, long, value_parser, default_value_t = 4)]
+    nb_threads: usize,
+}
+
+fn main() {
+    let args = Args::parse();
+    let files = match get_all_ovh_files_with(&args.dir, &TimestampExtractor::default()) {
+        Ok((f, skipped)) => {
+            for file in skipped.iter() {
+                println!("Skipping {}", file);
+            }
+            f
+        }
+        Err(e) => panic!("Error when getting the files: {}", e),
+    };
+    let cache = args.cache_dir.as_ref().map(|dir| {
+        SnapshotCache::new(dir)
+            .unwrap_or_else(|e| panic!("Impossible to use the cache directory {}: {}", dir, e))
+    });
+
+    // Snapshots come in timestamp order, as required by the store
+    let mut store = LoadStore::new();
+    let mut nb_errors = 0;
+    for parsed in SnapshotStream::new(&files, args.nb_threads, cache.clone()) {
+        match parsed {
+            Ok(data) => {
+                store.push(&data);
+            }
+            Err(e) => {
+                println!("Parsing error: {}", e);
+                nb_errors += 1;
+            }
+        }
+    }
+    for failure in cache.iter().flat_map(|c| c.take_store_failures()) {
+        println!("Could not cache {}", failure);
+    }
+    println!(
+        "Stored {} snapshots ({} could not be parsed)",
+        store.nb_snapshots(),
+        nb_errors
+    );
+    store
+        .save(&args.output)
+        .unwrap_or_else(|e| panic!("Impossible to write {}: {}", args.output, e));
+}
@@ -0,0 +1,72 @@
+use clap::Parser;
+use ovh_parsing::{
+    get_files::get_all_ovh_files_with,
+    stream::SnapshotStream,
+    upgrades::{write_changes_csv, CapacityDetector},
+    SnapshotCache, TimestampExtractor,
+};
+use std::fs::File;
+
+/// Detects the persistent changes in the number of parallel links between two routers,
+/// with the average load of the links before and after
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Path to the directory containing the data
+    #[clap(short, long, value_parser)]
+    dir: String,
+    /// Output CSV file
+    #[clap(short, long, value_parser)]
+    output: String,
+    /// Number of consecutive snapshots a new number of links must last to be reported
+    #[clap(long, value_parser, default_value_t = 12)]
+    persistence: usize,
+    /// Number of snapshots over which the load is averaged, before and after a change
+    #[clap(long, value_parser, default_value_t = 288)]
+    window: usize,
+    /// Number of threads to parse the YAML files
+    #[clap(short, long, value_parser, default_value_t = 4)]
+    nb_threads: usize,
+    /// Directory of the binary snapshot cache
+    #[clap(long, value_parser)]
+    cache_dir: Option<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let files = match get_all_ovh_files_with(&args.dir, &TimestampExtractor::default()) {
+        Ok((f, skipped)) => {
+            for file in skipped.iter() {
+                println!("Skipping {}", file);
+            }
+            f
+        }
+        Err(e) => panic!("Error when getting the files: {}", e),
+    };
+    let cache = args.cache_dir.as_ref().map(|dir| {
+        SnapshotCache::new(dir)
+            .unwrap_or_else(|e| panic!("Impossible to use the cache directory {}: {}", dir, e))
+    });
+
+    let mut detector = CapacityDetector::new(args.persistence, args.window);
+    for parsed in SnapshotStream::new(&files, args.nb_threads, cache.clone()) {
+        match parsed {
+            Ok(data) => detector.push(&data),
+            Err(e) => println!("Parsing error: {}", e),
+        }
+    }
+    for failure in cache.iter().flat_map(|c| c.take_store_failures()) {
+        println!("Could not cache {}", failure);
+    }
+    let changes = detector.finish();
+
+    let fd = File::create(&args.output)
+        .unwrap_or_else(|e| panic!("Impossible to create {}: {}", args.output, e));
+    write_changes_csv(&changes, fd)
+        .unwrap_or_else(|e| panic!("Impossible to write {}: {}", args.output, e));
+    println!(
+        "{} upgrades and {} downgrades",
+        changes.iter().filter(|c| c.is_upgrade()).count(),
+        changes.iter().filter(|c| !c.is_upgrade()).count()
+    );
+}
@@ -0,0 +1,90 @@
+use clap::Parser;
+use ovh_parsing::{
+    capacity::{write_external_csv, write_totals_csv, CapacityModel, TrafficTotals},
+    get_files::get_all_ovh_files_with,
+    stream::SnapshotStream,
+    SnapshotCache, TimestampExtractor,
+};
+use std::error::Error;
+use std::fs::File;
+use std::sync::Arc;
+
+/// Writes the time series of the traffic, in Gbps, between OVH and the external peers
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Path to the directory containing the data
+    #[clap(short, long, value_parser)]
+    dir: String,
+    /// Output CSV file
+    #[clap(short, long, value_parser)]
+    output: String,
+    /// YAML capacity table. All the links have the default capacity otherwise
+    #[clap(short, long, value_parser)]
+    capacities: Option<String>,
+    /// One row per peer and snapshot, instead of the total of each snapshot
+    #[clap(long, value_parser)]
+    per_peer: bool,
+    /// Also write the traffic of each router, peer and PoP in the last snapshot to this CSV file
+    #[clap(long, value_parser)]
+    last_totals: Option<String>,
+    /// Number of threads to parse the YAML files
+    #[clap(short, long, value_parser, default_value_t = 4)]
+    nb_threads: usize,
+    /// Directory of the binary snapshot cache
+    #[clap(long, value_parser)]
+    cache_dir: Option<String>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let files = match get_all_ovh_files_with(&args.dir, &TimestampExtractor::default()) {
+        Ok((f, skipped)) => {
+            for file in skipped.iter() {
+                println!("Skipping {}", file);
+            }
+            f
+        }
+        Err(e) => panic!("Error when getting the files: {}", e),
+    };
+    let cache = args.cache_dir.as_ref().map(|dir| {
+        SnapshotCache::new(dir)
+            .unwrap_or_else(|e| panic!("Impossible to use the cache directory {}: {}", dir, e))
+    });
+    let model = match &args.capacities {
+        Some(path) => CapacityModel::from_yaml_file(path)
+            .unwrap_or_else(|e| panic!("Impossible to read the capacities {}: {}", path, e)),
+        None => CapacityModel::default(),
+    };
+    let model = Arc::new(model);
+
+    // The totals are computed by the parsing threads
+    let task_cache = cache.clone();
+    let stream = SnapshotStream::with_task(&files, args.nb_threads, move |file| {
+        match &task_cache {
+            Some(cache) => cache.parse(file),
+            None => file.parse(),
+        }
+        .map(|data| TrafficTotals::of(&data, &model))
+    });
+    let mut last = None;
+    let totals = stream.filter_map(|parsed| match parsed {
+        Ok(totals) => {
+            last = Some(totals.clone());
+            Some(totals)
+        }
+        Err(e) => {
+            println!("Parsing error: {}", e);
+            None
+        }
+    });
+    write_external_csv(totals, args.per_peer, File::create(&args.output)?)?;
+    for failure in cache.iter().flat_map(|c| c.take_store_failures()) {
+        println!("Could not cache {}", failure);
+    }
+
+    if let (Some(path), Some(totals)) = (&args.last_totals, last) {
+        write_totals_csv(&totals, File::create(path)?)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use chrono::{DateTime, NaiveDateTime};
+use clap::Parser;
+use ovh_parsing::{
+    extract::{extract_loads, write_long_csv, NamePattern},
+    get_files::get_all_ovh_files_with,
+    load_store::LoadStore,
+    stream::SnapshotStream,
+    FileMetadata, SnapshotCache, TimestampExtractor,
+};
+use std::fs::File;
+use std::ops::{Bound, RangeBounds};
+use std::path::Path;
+
+/// Extracts the load time series of the links between the routers matching name patterns
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Path to the directory containing the data
+    #[clap(short, long, value_parser)]
+    dir: Option<String>,
+    /// Load store built by `build_load_store`, used instead of parsing the snapshots
+    #[clap(long, value_parser)]
+    store: Option<String>,
+    /// Pattern of the router names, where `*` matches any characters and `?` a single one
+    #[clap(short, long, value_parser, default_value = "*")]
+    router: NamePattern,
+    /// Pattern of the peer names
+    #[clap(short, long, value_parser, default_value = "*")]
+    peer: NamePattern,
+    /// Only keep the snapshots from this unix timestamp (included)
+    #[clap(long, value_parser)]
+    start_timestamp: Option<i64>,
+    /// Only keep the snapshots up to this unix timestamp (included)
+    #[clap(long, value_parser)]
+    stop_timestamp: Option<i64>,
+    /// Output directory of the `<router>_<peer>.csv` files
+    #[clap(short, long, value_parser, default_value = ".")]
+    output_dir: String,
+    /// Also write all the loads in this CSV file, one row per link and snapshot
+    #[clap(long, value_parser)]
+    long_output: Option<String>,
+    /// Number of threads to parse the YAML files
+    #[clap(short, long, value_parser, default_value_t = 4)]
+    nb_threads: usize,
+    /// Directory of the binary snapshot cache
+    #[clap(long, value_parser)]
+    cache_dir: Option<String>,
+}
+
+fn to_bound(timestamp: Option<i64>) -> Bound<NaiveDateTime> {
+    match timestamp {
+        Some(t) => Bound::Included(
+            DateTime::from_timestamp(t, 0)
+                .unwrap_or_else(|| panic!("Invalid timestamp: {}", t))
+                .naive_utc(),
+        ),
+        None => Bound::Unbounded,
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let range = (
+        to_bound(args.start_timestamp),
+        to_bound(args.stop_timestamp),
+    );
+
+    let store = match (&args.store, &args.dir) {
+        (Some(path), _) => LoadStore::load(path)
+            .unwrap_or_else(|e| panic!("Impossible to load the store {}: {}", path, e)),
+        (None, Some(dir)) => {
+            let files = match get_all_ovh_files_with(dir, &TimestampExtractor::default()) {
+                Ok((f, skipped)) => {
+                    for file in skipped.iter() {
+                        println!("Skipping {}", file);
+                    }
+                    f
+                }
+                Err(e) => panic!("Error when getting the files: {}", e),
+            };
+            // Only the snapshots in the time range are parsed
+            let files = files
+                .into_iter()
+                .filter(|f| range.contains(&f.timestamp))
+                .collect::<Vec<FileMetadata>>();
+            let cache = args.cache_dir.as_ref().map(|dir| {
+                SnapshotCache::new(dir).unwrap_or_else(|e| {
+                    panic!("Impossible to use the cache directory {}: {}", dir, e)
+                })
+            });
+            let mut store = LoadStore::new();
+            for parsed in SnapshotStream::new(&files, args.nb_threads, cache.clone()) {
+                match parsed {
+                    Ok(data) => {
+                        store.push(&data);
+                    }
+                    Err(e) => println!("Parsing error: {}", e),
+                }
+            }
+            for failure in cache.iter().flat_map(|c| c.take_store_failures()) {
+                println!("Could not cache {}", failure);
+            }
+            store
+        }
+        (None, None) => panic!("Either a directory of snapshots or a load store is needed"),
+    };
+
+    let tables = extract_loads(&store, &args.router, &args.peer, range);
+    for table in tables.iter() {
+        let path = Path::new(&args.output_dir).join(format!("{}_{}.csv", table.router, table.peer));
+        let fd = File::create(&path)
+            .unwrap_or_else(|e| panic!("Impossible to create {}: {}", path.display(), e));
+        table
+            .write_wide_csv(fd)
+            .unwrap_or_else(|e| panic!("Impossible to write {}: {}", path.display(), e));
+    }
+    if let Some(output) = &args.long_output {
+        let fd = File::create(output)
+            .unwrap_or_else(|e| panic!("Impossible to create {}: {}", output, e));
+        write_long_csv(&tables, fd)
+            .unwrap_or_else(|e| panic!("Impossible to write {}: {}", output, e));
+    }
+    println!(
+        "Extracted {} adjacencies over {} snapshots",
+        tables.len(),
+        store
+            .timestamps()
+            .iter()
+            .filter(|t| range.contains(*t))
+            .count()
+    );
+}
@@ -0,0 +1,91 @@
+use chrono::DateTime;
+use clap::Parser;
+use csv::Writer;
+use ovh_parsing::{graph::Graph, parse_yaml, OvhNodeFilter};
+use std::error::Error;
+
+/// Computes the paths, components and centralities of the router graph of a snapshot
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Snapshot file
+    #[clap(value_parser)]
+    snapshot: String,
+    /// Only keep the OVH routers, without the external peers
+    #[clap(long, value_parser)]
+    internal_only: bool,
+    /// Output CSV file with the centralities of each router
+    #[clap(short, long, value_parser)]
+    output: Option<String>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    // Set a dummy timestamp, not important here
+    let data = parse_yaml(
+        &args.snapshot,
+        DateTime::from_timestamp(100, 0).unwrap().naive_utc(),
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+    let graph = Graph::from_data_with(
+        &data,
+        match args.internal_only {
+            true => OvhNodeFilter::Ovh,
+            false => OvhNodeFilter::All,
+        },
+    );
+
+    let components = graph.components();
+    println!(
+        "{} routers, {} adjacencies, {} connected components (the largest with {} routers)",
+        graph.nb_nodes(),
+        graph.nb_edges(),
+        components.len(),
+        components.first().map(|c| c.len()).unwrap_or_default()
+    );
+    println!("Diameter: {} hops", graph.diameter().unwrap_or_default());
+    let articulation_points = graph.articulation_points();
+    println!("{} articulation points:", articulation_points.len());
+    for node in articulation_points.iter() {
+        println!("    {}", graph.node(*node).name);
+    }
+    let bridges = graph.bridges();
+    println!("{} bridges:", bridges.len());
+    for edge in bridges.into_iter().map(|e| graph.edge(e)) {
+        println!(
+            "    {} - {} ({} links)",
+            graph.node(edge.a).name,
+            graph.node(edge.b).name,
+            edge.nb_links()
+        );
+    }
+
+    if let Some(output) = &args.output {
+        let degree = graph.degree_centrality();
+        let betweenness = graph.betweenness_centrality();
+        let closeness = graph.closeness_centrality();
+        let mut wrt = Writer::from_path(output)?;
+        wrt.write_record([
+            "router",
+            "external",
+            "degree",
+            "degree_centrality",
+            "betweenness",
+            "closeness",
+            "articulation_point",
+        ])?;
+        for (id, node) in graph.nodes().iter().enumerate() {
+            wrt.write_record([
+                node.name.to_string(),
+                node.external.to_string(),
+                graph.degree(id).to_string(),
+                format!("{:.4}", degree[id]),
+                format!("{:.4}", betweenness[id]),
+                format!("{:.4}", closeness[id]),
+                articulation_points.contains(&id).to_string(),
+            ])?;
+        }
+        wrt.flush()?;
+    }
+    Ok(())
+}
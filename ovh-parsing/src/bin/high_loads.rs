@@ -0,0 +1,72 @@
+use chrono::Duration;
+use clap::Parser;
+use ovh_parsing::{
+    get_files::get_all_ovh_files_with,
+    high_loads::{write_episodes_csv, HighLoadDetector},
+    stream::SnapshotStream,
+    SnapshotCache, TimestampExtractor,
+};
+use std::fs::File;
+
+/// Finds the episodes during which links are highly loaded
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Path to the directory containing the data
+    #[clap(short, long, value_parser)]
+    dir: String,
+    /// Output CSV file
+    #[clap(short, long, value_parser)]
+    output: String,
+    /// Loads (%) from this value are high
+    #[clap(short, long, value_parser, default_value_t = 90)]
+    threshold: u32,
+    /// High loads at most this number of minutes apart belong to the same episode
+    #[clap(long, value_parser, default_value_t = 60)]
+    max_gap: i64,
+    /// Number of threads to parse the YAML files
+    #[clap(short, long, value_parser, default_value_t = 4)]
+    nb_threads: usize,
+    /// Directory of the binary snapshot cache
+    #[clap(long, value_parser)]
+    cache_dir: Option<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let files = match get_all_ovh_files_with(&args.dir, &TimestampExtractor::default()) {
+        Ok((f, skipped)) => {
+            for file in skipped.iter() {
+                println!("Skipping {}", file);
+            }
+            f
+        }
+        Err(e) => panic!("Error when getting the files: {}", e),
+    };
+    let cache = args.cache_dir.as_ref().map(|dir| {
+        SnapshotCache::new(dir)
+            .unwrap_or_else(|e| panic!("Impossible to use the cache directory {}: {}", dir, e))
+    });
+
+    let mut detector = HighLoadDetector::new(args.threshold, Duration::minutes(args.max_gap));
+    for parsed in SnapshotStream::new(&files, args.nb_threads, cache.clone()) {
+        match parsed {
+            Ok(data) => detector.push(&data),
+            Err(e) => println!("Parsing error: {}", e),
+        }
+    }
+    for failure in cache.iter().flat_map(|c| c.take_store_failures()) {
+        println!("Could not cache {}", failure);
+    }
+    let episodes = detector.finish();
+
+    let fd = File::create(&args.output)
+        .unwrap_or_else(|e| panic!("Impossible to create {}: {}", args.output, e));
+    write_episodes_csv(&episodes, fd)
+        .unwrap_or_else(|e| panic!("Impossible to write {}: {}", args.output, e));
+    println!(
+        "{} high-load episodes ({} truncated by a gap or the end of the data)",
+        episodes.len(),
+        episodes.iter().filter(|e| e.truncated).count()
+    );
+}
@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::DateTime;
 use core::panic;
 use ovh_parsing::{parse_yaml, OvhData, OvhNodeFilter, Router};
 use std::{collections::HashMap, env};
@@ -61,17 +61,19 @@ fn try_and_connect_network(
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 4 {
+    if args.len() < 5 {
         panic!(
-            "Usage: {} <Europe> <America> <Asia-Pacific> <World>",
+            "Usage: {} <Europe> <America> <Asia-Pacific> <World> [output file]",
             args[0]
         );
     }
 
-    let europe_data = parse_yaml(&args[1], NaiveDateTime::from_timestamp(100, 100)).unwrap();
-    let america_data = parse_yaml(&args[2], NaiveDateTime::from_timestamp(100, 100)).unwrap();
-    let asia_data = parse_yaml(&args[3], NaiveDateTime::from_timestamp(100, 100)).unwrap();
-    let world_data = parse_yaml(&args[4], NaiveDateTime::from_timestamp(100, 100)).unwrap();
+    // Set a dummy timestamp, not important here
+    let dummy_timestamp = DateTime::from_timestamp(100, 100).unwrap().naive_utc();
+    let europe_data = parse_yaml(&args[1], dummy_timestamp).unwrap_or_else(|e| panic!("{}", e));
+    let america_data = parse_yaml(&args[2], dummy_timestamp).unwrap_or_else(|e| panic!("{}", e));
+    let asia_data = parse_yaml(&args[3], dummy_timestamp).unwrap_or_else(|e| panic!("{}", e));
+    let world_data = parse_yaml(&args[4], dummy_timestamp).unwrap_or_else(|e| panic!("{}", e));
 
     println!(
         "Europe number of links: {}",
@@ -212,4 +214,11 @@ fn main() {
         "Total number of external links: {}",
         total.get_nb_links(OvhNodeFilter::External)
     );
+
+    // Save the merged network, so that it can be used as input by the other binaries
+    if let Some(output) = args.get(5) {
+        total
+            .write_to_file(output)
+            .unwrap_or_else(|e| panic!("Impossible to write {}: {}", output, e));
+    }
 }
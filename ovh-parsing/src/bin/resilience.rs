@@ -0,0 +1,132 @@
+use clap::Parser;
+use ovh_parsing::{
+    buckets::BucketUnit,
+    get_files::get_all_ovh_files_with,
+    resilience::{
+        write_bridges_csv, write_cuts_csv, write_history_csv, write_single_homed_csv,
+        ResilienceHistory, ResilienceReport,
+    },
+    stream::SnapshotStream,
+    SnapshotCache, TimestampExtractor,
+};
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+/// Reports the single-homed routers, the bridges and the minimum cuts of the PoPs over time
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Path to the directory containing the data
+    #[clap(short, long, value_parser)]
+    dir: String,
+    /// Output directory of the CSV files
+    #[clap(short, long, value_parser, default_value = ".")]
+    output_dir: String,
+    /// Time buckets: `hour`, `day`, `week` (ISO), `month`, `quarter` or a fixed duration such as `6h`
+    #[clap(long, value_parser, default_value = "month")]
+    aggregate_by: BucketUnit,
+    /// Only analyse one snapshot out of this number
+    #[clap(short, long, value_parser, default_value_t = 1)]
+    step: usize,
+    /// Number of threads to parse the YAML files
+    #[clap(short, long, value_parser, default_value_t = 4)]
+    nb_threads: usize,
+    /// Directory of the binary snapshot cache
+    #[clap(long, value_parser)]
+    cache_dir: Option<String>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let mut files = match get_all_ovh_files_with(&args.dir, &TimestampExtractor::default()) {
+        Ok((f, skipped)) => {
+            for file in skipped.iter() {
+                println!("Skipping {}", file);
+            }
+            f
+        }
+        Err(e) => panic!("Error when getting the files: {}", e),
+    };
+    files.sort_by_key(|f| f.timestamp);
+    let files = files
+        .into_iter()
+        .step_by(args.step.max(1))
+        .collect::<Vec<_>>();
+    let cache = args.cache_dir.as_ref().map(|dir| {
+        SnapshotCache::new(dir)
+            .unwrap_or_else(|e| panic!("Impossible to use the cache directory {}: {}", dir, e))
+    });
+
+    // The reports are computed by the parsing threads
+    let task_cache = cache.clone();
+    let stream = SnapshotStream::with_task(&files, args.nb_threads, move |file| {
+        match &task_cache {
+            Some(cache) => cache.parse(file),
+            None => file.parse(),
+        }
+        .map(|data| ResilienceReport::of(&data))
+    });
+    let mut history = ResilienceHistory::new(args.aggregate_by);
+    let mut last = None;
+    for parsed in stream {
+        match parsed {
+            Ok(report) => {
+                history.push(&report);
+                last = Some(report);
+            }
+            Err(e) => println!("Parsing error: {}", e),
+        }
+    }
+    for failure in cache.iter().flat_map(|c| c.take_store_failures()) {
+        println!("Could not cache {}", failure);
+    }
+
+    if let Some(report) = last {
+        println!("Last snapshot ({}):", report.timestamp);
+        println!("    {} single-homed routers:", report.single_homed.len());
+        for single_homed in report.single_homed.iter() {
+            println!(
+                "        {} (upstream: {})",
+                single_homed.router,
+                single_homed.upstream.as_deref().unwrap_or("none")
+            );
+        }
+        println!("    {} bridges:", report.bridges.len());
+        for bridge in report.bridges.iter() {
+            println!(
+                "        {} - {} ({} links)",
+                bridge.a, bridge.b, bridge.nb_links
+            );
+        }
+        println!("    Minimum cuts towards the border routers of the other PoPs:");
+        for cut in report.cuts.iter() {
+            match cut.cut {
+                Some(value) => println!(
+                    "        {} ({} routers): {} links over {} adjacencies",
+                    cut.pop,
+                    cut.nb_routers,
+                    value,
+                    cut.adjacencies.len()
+                ),
+                None => println!(
+                    "        {} ({} routers): no border router elsewhere",
+                    cut.pop, cut.nb_routers
+                ),
+            }
+        }
+    }
+
+    let output = Path::new(&args.output_dir);
+    write_history_csv(&history, File::create(output.join("resilience.csv"))?)?;
+    write_single_homed_csv(
+        &history,
+        File::create(output.join("resilience-single-homed.csv"))?,
+    )?;
+    write_bridges_csv(
+        &history,
+        File::create(output.join("resilience-bridges.csv"))?,
+    )?;
+    write_cuts_csv(&history, File::create(output.join("resilience-cuts.csv"))?)?;
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use clap::Parser;
+use ovh_parsing::{
+    diff::SnapshotDiff, get_files::get_all_ovh_files_with, parse_yaml, stream::SnapshotStream,
+    FileMetadata, OvhData, TimestampExtractor,
+};
+use std::path::Path;
+
+/// Prints the changes between two snapshot files, or between each pair of
+/// consecutive snapshots of a directory
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Either a directory of snapshots, or two snapshot files
+    #[clap(value_parser, required = true, max_values = 2)]
+    paths: Vec<String>,
+    /// Minimum load variation of a link, in percentage points, to be reported
+    #[clap(short = 't', long, value_parser, default_value_t = 20)]
+    load_threshold: u32,
+    /// Number of threads to parse the YAML files
+    #[clap(short, long, value_parser, default_value_t = 4)]
+    nb_threads: usize,
+}
+
+fn print_diff(diff: &SnapshotDiff) {
+    println!(
+        "=== {} -> {}: {} changes",
+        diff.from,
+        diff.to,
+        diff.changes.len()
+    );
+    for change in diff.changes.iter() {
+        println!("{}", change);
+    }
+}
+
+/// Parses a single snapshot file. Files without a timestamp in their name,
+/// e.g., the output of `link_world`, are still accepted.
+fn parse_file(filepath: &str) -> OvhData {
+    let parsed = match FileMetadata::path_to_file_metadata(Path::new(filepath)) {
+        Some(file) => file.parse(),
+        None => parse_yaml(filepath, Default::default()),
+    };
+    parsed.unwrap_or_else(|e| panic!("Impossible to parse {}: {}", filepath, e))
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if let [before, after] = &args.paths[..] {
+        print_diff(&parse_file(before).diff_with(&parse_file(after), args.load_threshold));
+        return;
+    }
+
+    let dir = &args.paths[0];
+    let files = match get_all_ovh_files_with(dir, &TimestampExtractor::default()) {
+        Ok((f, skipped)) => {
+            for file in skipped.iter() {
+                println!("Skipping {}", file);
+            }
+            f
+        }
+        Err(e) => panic!("Error when getting the files: {}", e),
+    };
+    // Only the previous snapshot is kept in memory
+    let mut previous: Option<OvhData> = None;
+    for parsed in SnapshotStream::new(&files, args.nb_threads, None) {
+        match parsed {
+            Ok(data) => {
+                if let Some(previous) = &previous {
+                    print_diff(&previous.diff_with(&data, args.load_threshold));
+                }
+                previous = Some(data);
+            }
+            Err(e) => println!("Parsing error: {}", e),
+        }
+    }
+}
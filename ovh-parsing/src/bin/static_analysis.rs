@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::DateTime;
 use ovh_parsing::{parse_yaml, write_in_csv, Link, OvhData, OvhNodeFilter, Router};
 use std::env;
 use std::error::Error;
@@ -43,8 +43,12 @@ fn main() {
     }
 
     // Set a dummy timestamp, not important here
-    let data = parse_yaml(&args[1], NaiveDateTime::from_timestamp(100, 0)).unwrap();
-    let data_routers = data.data.iter().map(|(_, v)| v).collect::<Vec<&Router>>();
+    let data = parse_yaml(
+        &args[1],
+        DateTime::from_timestamp(100, 0).unwrap().naive_utc(),
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+    let data_routers = data.data.values().collect::<Vec<&Router>>();
     static_node_degree_with_ecmp(&data_routers, "../csv/static_node_degree.csv").unwrap();
 
     let data_external = data.get_peering_routers();
@@ -0,0 +1,86 @@
+use chrono::Duration;
+use clap::Parser;
+use ovh_parsing::{
+    timeline::{
+        write_coverage_csv, write_distances_csv, write_gaps_csv, write_intervals_csv, Timeline,
+        DEFAULT_MAX_DISTANCE,
+    },
+    Dataset, MapConfig,
+};
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+/// Reports the continuous collection intervals and the gaps in the data of each map
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Root directory of the dataset
+    #[clap(short, long, value_parser)]
+    dir: String,
+    /// YAML file mapping directory names to maps
+    #[clap(long, value_parser)]
+    map_config: Option<String>,
+    /// Snapshots further apart than this number of seconds delimit a gap
+    #[clap(long, value_parser, default_value_t = DEFAULT_MAX_DISTANCE)]
+    max_distance: i64,
+    /// Output directory of the CSV files
+    #[clap(short, long, value_parser, default_value = ".")]
+    output_dir: String,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let config = match &args.map_config {
+        Some(path) => MapConfig::from_yaml_file(path)
+            .unwrap_or_else(|e| panic!("Impossible to read the map config {}: {}", path, e)),
+        None => MapConfig::default(),
+    };
+    let dataset = Dataset::discover(&args.dir, &config)
+        .unwrap_or_else(|e| panic!("Impossible to explore the directory {}: {}", args.dir, e));
+
+    let max_distance = Duration::seconds(args.max_distance);
+    let mut timelines = dataset
+        .iter()
+        .map(|(map, files)| {
+            let timeline = Timeline::new(files.iter().map(|f| f.timestamp), max_distance);
+            (map.to_string(), timeline)
+        })
+        .collect::<Vec<(String, Timeline)>>();
+    if !dataset.unassigned.is_empty() {
+        let timestamps = dataset.unassigned.iter().map(|f| f.timestamp);
+        timelines.push((
+            "unassigned".to_string(),
+            Timeline::new(timestamps, max_distance),
+        ));
+    }
+
+    for (map, timeline) in timelines.iter() {
+        let coverage = timeline.daily_coverage();
+        let longest_gap = timeline.gaps().iter().map(|g| g.duration()).max();
+        println!(
+            "{}: {} snapshots, {} intervals, longest gap of {} hours, mean daily coverage of {:.2}%",
+            map,
+            timeline.timestamps().len(),
+            timeline.intervals().len(),
+            longest_gap.unwrap_or_else(Duration::zero).num_hours(),
+            coverage.iter().map(|(_, c)| c).sum::<f64>() * 100.0 / coverage.len().max(1) as f64,
+        );
+    }
+
+    let output = Path::new(&args.output_dir);
+    write_intervals_csv(
+        &timelines,
+        File::create(output.join("timeline-intervals.csv"))?,
+    )?;
+    write_gaps_csv(&timelines, File::create(output.join("timeline-gaps.csv"))?)?;
+    write_distances_csv(
+        &timelines,
+        File::create(output.join("timeline-distances.csv"))?,
+    )?;
+    write_coverage_csv(
+        &timelines,
+        File::create(output.join("timeline-coverage.csv"))?,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use clap::Parser;
+use ovh_parsing::{
+    events::{write_events_csv, EventLog},
+    get_files::get_all_ovh_files_with,
+    stream::SnapshotStream,
+    SnapshotCache, TimestampExtractor,
+};
+use std::fs::File;
+
+/// Builds the chronological log of the topology changes of a directory of snapshots
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Path to the directory containing the data
+    #[clap(short, long, value_parser)]
+    dir: String,
+    /// Output CSV file. The events are printed if not given
+    #[clap(short, long, value_parser)]
+    output: Option<String>,
+    /// Entries missing in at most this number of consecutive snapshots are reported as glitches
+    #[clap(long, value_parser, default_value_t = 1)]
+    debounce: usize,
+    /// Number of threads to parse the YAML files
+    #[clap(short, long, value_parser, default_value_t = 4)]
+    nb_threads: usize,
+    /// Directory of the binary snapshot cache
+    #[clap(long, value_parser)]
+    cache_dir: Option<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let files = match get_all_ovh_files_with(&args.dir, &TimestampExtractor::default()) {
+        Ok((f, skipped)) => {
+            for file in skipped.iter() {
+                println!("Skipping {}", file);
+            }
+            f
+        }
+        Err(e) => panic!("Error when getting the files: {}", e),
+    };
+    let cache = args.cache_dir.as_ref().map(|dir| {
+        SnapshotCache::new(dir)
+            .unwrap_or_else(|e| panic!("Impossible to use the cache directory {}: {}", dir, e))
+    });
+
+    let mut log = EventLog::new(args.debounce);
+    for parsed in SnapshotStream::new(&files, args.nb_threads, cache.clone()) {
+        match parsed {
+            Ok(data) => log.push(data),
+            Err(e) => println!("Parsing error: {}", e),
+        }
+    }
+    for failure in cache.iter().flat_map(|c| c.take_store_failures()) {
+        println!("Could not cache {}", failure);
+    }
+    let nb_snapshots = log.nb_snapshots();
+    let events = log.finish();
+
+    match &args.output {
+        Some(output) => {
+            let fd = File::create(output)
+                .unwrap_or_else(|e| panic!("Impossible to create {}: {}", output, e));
+            write_events_csv(&events, fd)
+                .unwrap_or_else(|e| panic!("Impossible to write {}: {}", output, e));
+        }
+        None => events.iter().for_each(|event| println!("{}", event)),
+    }
+    println!("{} events over {} snapshots", events.len(), nb_snapshots);
+}
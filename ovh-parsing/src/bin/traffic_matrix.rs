@@ -0,0 +1,71 @@
+use chrono::DateTime;
+use clap::Parser;
+use ovh_parsing::{
+    parse_yaml,
+    tomography::{write_matrix_csv, write_residuals_csv, Granularity, Tomography},
+    Link,
+};
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+/// Estimates the traffic matrix of a snapshot from its link loads
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Snapshot file
+    #[clap(value_parser)]
+    snapshot: String,
+    /// Endpoints of the matrix: `pop-to-pop`, or `pop-to-peer` to add the external peers
+    #[clap(short, long, value_parser, default_value = "pop-to-pop")]
+    granularity: Granularity,
+    /// Capacity of every link, in Gbps
+    #[clap(short, long, value_parser, default_value_t = 100.0)]
+    capacity: f64,
+    /// Iterations of the least-squares refinement, 0 to keep the gravity estimate
+    #[clap(long, value_parser, default_value_t = 1000)]
+    iterations: usize,
+    /// Weight of the distance to the gravity estimate in the least-squares refinement
+    #[clap(long, value_parser, default_value_t = 0.01)]
+    prior_weight: f64,
+    /// Output directory of the CSV files
+    #[clap(short, long, value_parser, default_value = ".")]
+    output_dir: String,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    // Set a dummy timestamp, not important here
+    let data = parse_yaml(
+        &args.snapshot,
+        DateTime::from_timestamp(100, 0).unwrap().naive_utc(),
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+
+    let mut tomography = Tomography::new(args.granularity);
+    tomography.iterations = args.iterations;
+    tomography.prior_weight = args.prior_weight;
+    let matrix = tomography.estimate(&data, |_: &str, _: &str, _: &Link| args.capacity);
+
+    println!(
+        "{} demands estimated from {} links: RMSE of {:.2} percentage points, relative error of {:.2}%",
+        matrix.demands.len(),
+        matrix.observations.len(),
+        matrix.rmse(),
+        matrix.relative_error() * 100.0
+    );
+    let mut largest = matrix.demands.iter().collect::<Vec<_>>();
+    largest.sort_by(|a, b| b.estimate.total_cmp(&a.estimate));
+    println!("Largest demands (Gbps):");
+    for demand in largest.into_iter().take(10) {
+        println!(
+            "    {} -> {}: {:.2} (gravity: {:.2})",
+            demand.source, demand.destination, demand.estimate, demand.gravity
+        );
+    }
+
+    let output = Path::new(&args.output_dir);
+    write_matrix_csv(&matrix, File::create(output.join("traffic-matrix.csv"))?)?;
+    write_residuals_csv(&matrix, File::create(output.join("traffic-residuals.csv"))?)?;
+    Ok(())
+}
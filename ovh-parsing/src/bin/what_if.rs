@@ -0,0 +1,69 @@
+use chrono::DateTime;
+use clap::Parser;
+use ovh_parsing::{
+    parse_yaml,
+    simulation::{simulate, write_estimates_csv, Failure},
+};
+use std::error::Error;
+use std::fs::File;
+
+/// Estimates the effect of router, adjacency or link failures on a snapshot
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Snapshot file
+    #[clap(value_parser)]
+    snapshot: String,
+    /// Failure, as <router>, <router>:<peer> or <router>:<peer>:<label>. Can be repeated
+    #[clap(short, long = "fail", value_parser, required = true)]
+    failures: Vec<Failure>,
+    /// Output CSV file with the estimated loads of the links affected by the failures
+    #[clap(short, long, value_parser)]
+    output: Option<String>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    // Set a dummy timestamp, not important here
+    let data = parse_yaml(
+        &args.snapshot,
+        DateTime::from_timestamp(100, 0).unwrap().naive_utc(),
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+    for failure in args.failures.iter() {
+        if let Failure::Router(router) = failure {
+            if !data.data.contains_key(router) {
+                println!("Warning: no router {} in the snapshot", router);
+            }
+        }
+    }
+
+    let report = simulate(&data, &args.failures);
+    println!(
+        "{} routers disconnected from the external peers:",
+        report.disconnected.len()
+    );
+    for router in report.disconnected.iter() {
+        println!("    {}", router);
+    }
+    let overloaded = report.overloaded();
+    println!("{} links above 100%:", overloaded.len());
+    for link in overloaded {
+        println!(
+            "    {} -> {} {}: {}% -> {:.2}%",
+            link.router, link.peer, link.label, link.before, link.after
+        );
+    }
+    println!("{} flows without a route:", report.unrouted.len());
+    for unrouted in report.unrouted.iter() {
+        println!(
+            "    {} -> {}: {:.2}%",
+            unrouted.from, unrouted.to, unrouted.load
+        );
+    }
+
+    if let Some(output) = &args.output {
+        write_estimates_csv(&report.links, File::create(output)?)?;
+    }
+    Ok(())
+}
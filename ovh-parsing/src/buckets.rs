@@ -0,0 +1,220 @@
+//! Grouping of timestamped values into consecutive time buckets.
+//!
+//! Buckets are aligned on calendar boundaries (hours, days, ISO weeks starting on Monday,
+//! months and quarters), or on multiples of a fixed duration since the epoch. Every bucket
+//! between the first and the last value is returned, empty ones included, so that gaps in
+//! the data stay visible in the aggregated results.
+
+use chrono::prelude::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use chrono::{Datelike, Duration, Months, Timelike};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Size of the time buckets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BucketUnit {
+    Hour,
+    Day,
+    /// ISO week, starting on Monday.
+    Week,
+    Month,
+    Quarter,
+    /// Any positive duration, aligned on the epoch.
+    Fixed(Duration),
+}
+
+impl BucketUnit {
+    /// Start of the bucket containing `t`.
+    pub fn start_of(&self, t: NaiveDateTime) -> NaiveDateTime {
+        let midnight = |date: NaiveDate| NaiveDateTime::new(date, NaiveTime::MIN);
+        match self {
+            BucketUnit::Hour => midnight(t.date()) + Duration::hours(t.hour() as i64),
+            BucketUnit::Day => midnight(t.date()),
+            BucketUnit::Week => {
+                midnight(t.date()) - Duration::days(t.weekday().num_days_from_monday() as i64)
+            }
+            BucketUnit::Month => midnight(t.date().with_day(1).unwrap()),
+            BucketUnit::Quarter => {
+                midnight(NaiveDate::from_ymd_opt(t.year(), (t.month() - 1) / 3 * 3 + 1, 1).unwrap())
+            }
+            BucketUnit::Fixed(duration) => {
+                let step = duration.num_seconds().max(1);
+                let secs = t.and_utc().timestamp();
+                DateTime::from_timestamp(secs.div_euclid(step) * step, 0)
+                    .unwrap()
+                    .naive_utc()
+            }
+        }
+    }
+
+    /// Start of the bucket following the one starting at `start`.
+    /// The last bucket that can be represented ends at `NaiveDateTime::MAX`.
+    pub fn next(&self, start: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            BucketUnit::Hour => start.checked_add_signed(Duration::hours(1)),
+            BucketUnit::Day => start.checked_add_signed(Duration::days(1)),
+            BucketUnit::Week => start.checked_add_signed(Duration::weeks(1)),
+            BucketUnit::Month => start.checked_add_months(Months::new(1)),
+            BucketUnit::Quarter => start.checked_add_months(Months::new(3)),
+            BucketUnit::Fixed(duration) => {
+                start.checked_add_signed(Duration::seconds(duration.num_seconds().max(1)))
+            }
+        }
+        .unwrap_or(NaiveDateTime::MAX)
+    }
+}
+
+impl fmt::Display for BucketUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BucketUnit::Hour => write!(f, "hour"),
+            BucketUnit::Day => write!(f, "day"),
+            BucketUnit::Week => write!(f, "week"),
+            BucketUnit::Month => write!(f, "month"),
+            BucketUnit::Quarter => write!(f, "quarter"),
+            BucketUnit::Fixed(duration) => write!(f, "{}s", duration.num_seconds()),
+        }
+    }
+}
+
+impl FromStr for BucketUnit {
+    type Err = String;
+
+    /// `hour`, `day`, `week`, `month`, `quarter`, or a fixed duration such as
+    /// `90s`, `30min`, `6h` or `2d`.
+    fn from_str(s: &str) -> Result<BucketUnit, String> {
+        match s {
+            "hour" => return Ok(BucketUnit::Hour),
+            "day" => return Ok(BucketUnit::Day),
+            "week" => return Ok(BucketUnit::Week),
+            "month" => return Ok(BucketUnit::Month),
+            "quarter" => return Ok(BucketUnit::Quarter),
+            _ => {}
+        }
+        let split = s
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(|| format!("Missing unit in bucket duration: {}", s))?;
+        let value = s[..split]
+            .parse::<i64>()
+            .map_err(|_| format!("Invalid bucket duration: {}", s))?;
+        let duration = match &s[split..] {
+            "s" => Duration::try_seconds(value),
+            "min" => Duration::try_minutes(value),
+            "h" => Duration::try_hours(value),
+            "d" => Duration::try_days(value),
+            unit => return Err(format!("Unknown unit in bucket duration: {}", unit)),
+        }
+        .ok_or_else(|| format!("Bucket duration out of range: {}", s))?;
+        if duration <= Duration::zero() {
+            return Err(format!("Bucket duration must be positive: {}", s));
+        }
+        Ok(BucketUnit::Fixed(duration))
+    }
+}
+
+/// The values whose timestamp lies in `[start, end[`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bucket<T> {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub values: Vec<T>,
+}
+
+/// Groups `values` by bucket of `unit`, keeping their order within each bucket.
+/// All the buckets from the first value to the last are returned, empty ones included.
+/// No values gives no buckets.
+pub fn bucket_by<T, I, F>(values: I, unit: BucketUnit, timestamp: F) -> Vec<Bucket<T>>
+where
+    I: IntoIterator<Item = T>,
+    F: Fn(&T) -> NaiveDateTime,
+{
+    let mut grouped: BTreeMap<NaiveDateTime, Vec<T>> = BTreeMap::new();
+    for value in values {
+        grouped
+            .entry(unit.start_of(timestamp(&value)))
+            .or_default()
+            .push(value);
+    }
+    let last = match grouped.keys().next_back() {
+        Some(&last) => last,
+        None => return Vec::new(),
+    };
+
+    let mut buckets = Vec::with_capacity(grouped.len());
+    let mut start = *grouped.keys().next().unwrap();
+    while start <= last {
+        let end = unit.next(start);
+        buckets.push(Bucket {
+            start,
+            end,
+            values: grouped.remove(&start).unwrap_or_default(),
+        });
+        if end == start {
+            break;
+        }
+        start = end;
+    }
+    buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_bucket_starts() {
+        let at = t("2022-05-18 13:47");
+        assert_eq!(BucketUnit::Hour.start_of(at), t("2022-05-18 13:00"));
+        assert_eq!(BucketUnit::Day.start_of(at), t("2022-05-18 00:00"));
+        assert_eq!(BucketUnit::Week.start_of(at), t("2022-05-16 00:00"));
+        assert_eq!(BucketUnit::Month.start_of(at), t("2022-05-01 00:00"));
+        assert_eq!(BucketUnit::Quarter.start_of(at), t("2022-04-01 00:00"));
+        let six_hours = "6h".parse::<BucketUnit>().unwrap();
+        assert_eq!(six_hours.start_of(at), t("2022-05-18 12:00"));
+        assert_eq!(six_hours.next(t("2022-05-18 12:00")), t("2022-05-18 18:00"));
+        assert_eq!(
+            BucketUnit::Quarter.next(t("2022-10-01 00:00")),
+            t("2023-01-01 00:00")
+        );
+        assert!("0h".parse::<BucketUnit>().is_err());
+        assert!("fortnight".parse::<BucketUnit>().is_err());
+        assert!("200000000000d".parse::<BucketUnit>().is_err());
+        // The bucket holding the end of time is the last one
+        let eons = "100000000000d".parse::<BucketUnit>().unwrap();
+        assert_eq!(eons.next(t("2022-05-18 12:00")), NaiveDateTime::MAX);
+        assert_eq!(
+            BucketUnit::Hour.next(NaiveDateTime::MAX),
+            NaiveDateTime::MAX
+        );
+        assert_eq!(bucket_by(vec![at], eons, |v| *v).len(), 1);
+    }
+
+    #[test]
+    fn test_bucket_by() {
+        let values = vec![
+            t("2022-01-31 23:59"),
+            t("2022-01-02 00:00"),
+            t("2022-04-10 12:00"),
+        ];
+        let buckets = bucket_by(values, BucketUnit::Month, |v| *v);
+        assert_eq!(
+            buckets
+                .iter()
+                .map(|b| (b.start, b.values.len()))
+                .collect::<Vec<(NaiveDateTime, usize)>>(),
+            vec![
+                (t("2022-01-01 00:00"), 2),
+                (t("2022-02-01 00:00"), 0),
+                (t("2022-03-01 00:00"), 0),
+                (t("2022-04-01 00:00"), 1),
+            ]
+        );
+        assert_eq!(buckets[0].end, t("2022-02-01 00:00"));
+        assert!(bucket_by(Vec::<NaiveDateTime>::new(), BucketUnit::Day, |v| *v).is_empty());
+    }
+}
@@ -0,0 +1,411 @@
+//! Capacities of the links, to turn their loads into absolute traffic.
+//!
+//! The weather map only gives loads in percent. The capacity of a link, in Gbps, comes from
+//! the first of:
+//! 1. the entry of the capacity table for its label between its two routers;
+//! 2. the entry for all the links between its two routers;
+//! 3. the first class whose pattern matches the name of the router listing the link, then
+//!    the name of its peer;
+//! 4. the default capacity of external links (with an external peer) or internal ones.
+//!
+//! Entries of the table apply to both directions of the links.
+
+use crate::extract::NamePattern;
+use crate::{is_peer_from_name, pop_from_name, Link, OvhData};
+use chrono::NaiveDateTime;
+use csv::Writer;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+
+/// Default capacity of the links, in Gbps.
+pub const DEFAULT_CAPACITY: f64 = 100.0;
+
+/// Capacity of the links between `router` and `peer`, or only of the one labelled `label`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LinkEntry {
+    router: String,
+    peer: String,
+    label: Option<String>,
+    capacity: f64,
+}
+
+/// Capacity of the links of the routers whose name matches `routers`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClassEntry {
+    routers: String,
+    capacity: f64,
+}
+
+/// The capacity table as written in a configuration file, every field being optional.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CapacityFile {
+    internal: Option<f64>,
+    external: Option<f64>,
+    #[serde(default)]
+    classes: Vec<ClassEntry>,
+    #[serde(default)]
+    links: Vec<LinkEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CapacityModel {
+    /// Capacity of the links between two OVH routers.
+    pub internal: f64,
+    /// Capacity of the links with an external peer.
+    pub external: f64,
+    classes: Vec<(NamePattern, f64)>,
+    /// By pair of routers, the smallest name first.
+    adjacencies: HashMap<(String, String), f64>,
+    labels: HashMap<(String, String, String), f64>,
+}
+
+impl Default for CapacityModel {
+    fn default() -> Self {
+        CapacityModel::uniform(DEFAULT_CAPACITY)
+    }
+}
+
+/// `capacity`, if it is a positive number of Gbps.
+fn checked(capacity: f64, of: &str) -> Result<f64, String> {
+    match capacity.is_finite() && capacity > 0.0 {
+        true => Ok(capacity),
+        false => Err(format!("Invalid capacity {} for {}", capacity, of)),
+    }
+}
+
+/// Pair of routers in a fixed order, for both directions of a link.
+fn key(router: &str, peer: &str) -> (String, String) {
+    match router <= peer {
+        true => (router.to_string(), peer.to_string()),
+        false => (peer.to_string(), router.to_string()),
+    }
+}
+
+impl CapacityModel {
+    /// The same capacity for all the links.
+    pub fn uniform(capacity: f64) -> CapacityModel {
+        CapacityModel {
+            internal: capacity,
+            external: capacity,
+            classes: Vec::new(),
+            adjacencies: HashMap::new(),
+            labels: HashMap::new(),
+        }
+    }
+
+    /// Reads a YAML file such as
+    /// `{internal: 100, external: 10, classes: [{routers: "*-n7", capacity: 400}],
+    /// links: [{router: ams-1-n7, peer: AMS-IX, label: "#1", capacity: 100}]}`,
+    /// where `label` is optional. The missing defaults are `DEFAULT_CAPACITY`.
+    /// Unknown fields, and capacities that are not positive numbers, are rejected.
+    pub fn from_yaml_file(filepath: &str) -> Result<CapacityModel, Box<dyn Error>> {
+        let fd = File::open(filepath)?;
+        let file: CapacityFile = serde_yaml::from_reader(fd)?;
+        let mut model = CapacityModel {
+            internal: checked(file.internal.unwrap_or(DEFAULT_CAPACITY), "internal")?,
+            external: checked(file.external.unwrap_or(DEFAULT_CAPACITY), "external")?,
+            ..CapacityModel::default()
+        };
+        for class in file.classes {
+            let capacity = checked(class.capacity, &class.routers)?;
+            model.add_class(class.routers.parse()?, capacity);
+        }
+        for entry in file.links {
+            let of = format!("{} - {}", entry.router, entry.peer);
+            let capacity = checked(entry.capacity, &of)?;
+            match entry.label {
+                Some(label) => model.set_link(&entry.router, &entry.peer, &label, capacity),
+                None => model.set_adjacency(&entry.router, &entry.peer, capacity),
+            }
+        }
+        Ok(model)
+    }
+
+    /// Adds a class of routers, after the existing ones.
+    pub fn add_class(&mut self, routers: NamePattern, capacity: f64) {
+        self.classes.push((routers, capacity));
+    }
+
+    /// Sets the capacity of all the links between `router` and `peer`.
+    pub fn set_adjacency(&mut self, router: &str, peer: &str, capacity: f64) {
+        self.adjacencies.insert(key(router, peer), capacity);
+    }
+
+    /// Sets the capacity of the links labelled `label` between `router` and `peer`.
+    pub fn set_link(&mut self, router: &str, peer: &str, label: &str, capacity: f64) {
+        let (a, b) = key(router, peer);
+        self.labels.insert((a, b, label.to_string()), capacity);
+    }
+
+    /// Capacity, in Gbps, of the link `link` listed by `router` towards `peer`.
+    pub fn capacity_of(&self, router: &str, peer: &str, link: &Link) -> f64 {
+        let (a, b) = key(router, peer);
+        let label = (a.to_string(), b.to_string(), link.label.to_string());
+        if let Some(&capacity) = self.labels.get(&label) {
+            return capacity;
+        }
+        if let Some(&capacity) = self.adjacencies.get(&(a, b)) {
+            return capacity;
+        }
+        for name in [router, peer] {
+            if let Some((_, capacity)) = self.classes.iter().find(|(p, _)| p.matches(name)) {
+                return *capacity;
+            }
+        }
+        match is_peer_from_name(router) || is_peer_from_name(peer) {
+            true => self.external,
+            false => self.internal,
+        }
+    }
+
+    /// Traffic, in Gbps, of the link `link` listed by `router` towards `peer`.
+    pub fn traffic_of(&self, router: &str, peer: &str, link: &Link) -> f64 {
+        link.load as f64 * self.capacity_of(router, peer, link) / 100.0
+    }
+}
+
+/// Traffic sent and received, in Gbps.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Traffic {
+    pub outgoing: f64,
+    pub incoming: f64,
+}
+
+/// Total traffic of a snapshot.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrafficTotals {
+    pub timestamp: NaiveDateTime,
+    /// Traffic of each OVH router, on all its links.
+    pub routers: BTreeMap<String, Traffic>,
+    /// Traffic between OVH and each external peer, seen from OVH: the outgoing traffic
+    /// goes towards the peer.
+    pub peers: BTreeMap<String, Traffic>,
+    /// Traffic of each PoP with the other PoPs and the external peers.
+    pub pops: BTreeMap<String, Traffic>,
+}
+
+impl TrafficTotals {
+    pub fn of(data: &OvhData, model: &CapacityModel) -> TrafficTotals {
+        let mut totals = TrafficTotals {
+            timestamp: data.timestamp,
+            ..TrafficTotals::default()
+        };
+        for router in data.data.values() {
+            for (peer, links) in router.peers.iter() {
+                let traffic = links
+                    .iter()
+                    .map(|link| model.traffic_of(&router.name, peer, link))
+                    .sum::<f64>();
+                for (name, outgoing) in [(&router.name, true), (peer, false)] {
+                    if !is_peer_from_name(name) {
+                        totals
+                            .routers
+                            .entry(name.to_string())
+                            .or_default()
+                            .add(traffic, outgoing);
+                    }
+                }
+                match (is_peer_from_name(&router.name), is_peer_from_name(peer)) {
+                    (false, true) => {
+                        totals.peers.entry(peer.to_string()).or_default().outgoing += traffic
+                    }
+                    (true, false) => {
+                        totals
+                            .peers
+                            .entry(router.name.to_string())
+                            .or_default()
+                            .incoming += traffic
+                    }
+                    _ => (),
+                }
+                let (from, to) = (pop_from_name(&router.name), pop_from_name(peer));
+                if from != to {
+                    for (pop, outgoing) in [(from, true), (to, false)] {
+                        if let Some(pop) = pop {
+                            totals
+                                .pops
+                                .entry(pop.to_string())
+                                .or_default()
+                                .add(traffic, outgoing);
+                        }
+                    }
+                }
+            }
+        }
+        totals
+    }
+
+    /// Total traffic between OVH and the external peers, seen from OVH.
+    pub fn external(&self) -> Traffic {
+        self.peers
+            .values()
+            .fold(Traffic::default(), |total, t| Traffic {
+                outgoing: total.outgoing + t.outgoing,
+                incoming: total.incoming + t.incoming,
+            })
+    }
+}
+
+impl Traffic {
+    fn add(&mut self, traffic: f64, outgoing: bool) {
+        match outgoing {
+            true => self.outgoing += traffic,
+            false => self.incoming += traffic,
+        }
+    }
+}
+
+/// Writes the totals of a snapshot as `kind,name,outgoing,incoming`, with headers, where
+/// `kind` is `router`, `peer` or `pop`, and the traffic in Gbps.
+pub fn write_totals_csv<W: Write>(totals: &TrafficTotals, wrt: W) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record(["kind", "name", "outgoing", "incoming"])?;
+    for (kind, traffic) in [
+        ("router", &totals.routers),
+        ("peer", &totals.peers),
+        ("pop", &totals.pops),
+    ] {
+        for (name, traffic) in traffic {
+            wrt.write_record([
+                kind.to_string(),
+                name.to_string(),
+                format!("{:.2}", traffic.outgoing),
+                format!("{:.2}", traffic.incoming),
+            ])?;
+        }
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+/// Writes the external traffic of each snapshot as `timestamp,outgoing,incoming`, or
+/// `timestamp,peer,outgoing,incoming` with `per_peer`, with headers, unix timestamps and the
+/// traffic in Gbps, seen from OVH. The totals are written as they come.
+pub fn write_external_csv<I, W>(totals: I, per_peer: bool, wrt: W) -> Result<(), Box<dyn Error>>
+where
+    I: IntoIterator<Item = TrafficTotals>,
+    W: Write,
+{
+    let mut wrt = Writer::from_writer(wrt);
+    match per_peer {
+        true => wrt.write_record(["timestamp", "peer", "outgoing", "incoming"])?,
+        false => wrt.write_record(["timestamp", "outgoing", "incoming"])?,
+    }
+    for totals in totals {
+        let timestamp = totals.timestamp.and_utc().timestamp().to_string();
+        if per_peer {
+            for (peer, traffic) in totals.peers.iter() {
+                wrt.write_record([
+                    timestamp.clone(),
+                    peer.to_string(),
+                    format!("{:.2}", traffic.outgoing),
+                    format!("{:.2}", traffic.incoming),
+                ])?;
+            }
+        } else {
+            let traffic = totals.external();
+            wrt.write_record([
+                timestamp,
+                format!("{:.2}", traffic.outgoing),
+                format!("{:.2}", traffic.incoming),
+            ])?;
+        }
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Router;
+    use chrono::DateTime;
+
+    fn load(yaml: &str) -> Result<CapacityModel, Box<dyn Error>> {
+        let path = std::env::temp_dir().join(format!(
+            "ovh-capacities-{}-{:?}.yaml",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        std::fs::write(&path, yaml).unwrap();
+        let model = CapacityModel::from_yaml_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        model
+    }
+
+    #[test]
+    fn test_capacities_and_totals() {
+        let model = load(
+            "internal: 400\nexternal: 10\nclasses: [{routers: \"rbx-*\", capacity: 40}]\n\
+             links:\n  - {router: AMS-IX, peer: ams-1, capacity: 100}\n  \
+             - {router: ams-1, peer: AMS-IX, label: \"#2\", capacity: 200}\n",
+        )
+        .unwrap();
+
+        let link = |label: &str, load: u32| Link {
+            label: label.to_string(),
+            load,
+        };
+        assert_eq!(model.capacity_of("ams-1", "AMS-IX", &link("#1", 0)), 100.0);
+        assert_eq!(model.capacity_of("AMS-IX", "ams-1", &link("#2", 0)), 200.0);
+        assert_eq!(model.capacity_of("ams-1", "rbx-1", &link("#1", 0)), 40.0);
+        assert_eq!(model.capacity_of("ams-1", "ams-2", &link("#1", 0)), 400.0);
+        assert_eq!(model.capacity_of("ams-2", "DE-CIX", &link("#1", 0)), 10.0);
+
+        let mut data: HashMap<String, Router> = HashMap::new();
+        for (router, peer, link) in [
+            ("ams-1", "AMS-IX", link("#1", 10)),
+            ("ams-1", "AMS-IX", link("#2", 10)),
+            ("AMS-IX", "ams-1", link("#1", 50)),
+            ("ams-1", "ams-2", link("#1", 25)),
+            ("ams-1", "rbx-1", link("#1", 50)),
+        ] {
+            data.entry(router.to_string())
+                .or_insert_with(|| Router {
+                    name: router.to_string(),
+                    peers: HashMap::new(),
+                })
+                .peers
+                .entry(peer.to_string())
+                .or_default()
+                .push(link);
+        }
+        let data = OvhData {
+            timestamp: DateTime::from_timestamp(300, 0).unwrap().naive_utc(),
+            data,
+        };
+        let totals = TrafficTotals::of(&data, &model);
+        let traffic = |outgoing, incoming| Traffic { outgoing, incoming };
+        assert_eq!(totals.routers["ams-1"], traffic(150.0, 50.0));
+        assert_eq!(totals.routers["rbx-1"], traffic(0.0, 20.0));
+        assert_eq!(totals.peers["AMS-IX"], traffic(30.0, 50.0));
+        // The traffic between ams-1 and ams-2 stays in the PoP
+        assert_eq!(totals.pops["ams"], traffic(50.0, 50.0));
+        assert_eq!(totals.external(), traffic(30.0, 50.0));
+
+        let mut csv = Vec::new();
+        write_external_csv([totals], false, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "timestamp,outgoing,incoming\n300,30.00,50.00\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        assert!(load("internal: 0\n").is_err());
+        assert!(load("external: .nan\n").is_err());
+        assert!(load("classes: [{routers: \"rbx-*\", capacity: -40}]\n").is_err());
+        assert!(load("links: [{router: a, peer: b, capacity: 0}]\n").is_err());
+        // Misspelled fields are not silently ignored
+        assert!(load("internals: 400\n").is_err());
+        assert!(load("classes: [{router: \"rbx-*\", capacity: 40}]\n").is_err());
+        assert!(load("links: [{router: a, peer: b, lable: \"#1\", capacity: 10}]\n").is_err());
+    }
+}
@@ -0,0 +1,449 @@
+//! Chronological log of the topology changes of a map.
+//!
+//! Consecutive snapshots are compared with `OvhData::diff`. Entries that disappear
+//! and come back within a few snapshots are reported as a single glitch rather than
+//! as a removal followed by an addition.
+//! Internal adjacencies are reported once per pair of routers, and peering sessions
+//! from the side of the OVH router.
+
+use crate::diff::Change;
+use crate::{is_peer_from_name, OvhData};
+use chrono::prelude::NaiveDateTime;
+use csv::Writer;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+
+/// What changed in the topology.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    RouterAdded,
+    RouterRemoved,
+    /// A session with an external peer appeared.
+    PeeringAppeared,
+    PeeringDisappeared,
+    /// Two OVH routers are now connected.
+    AdjacencyAdded,
+    AdjacencyRemoved,
+    /// Links were added to the bundle between the two routers.
+    LinkAdded {
+        before: usize,
+        after: usize,
+    },
+    LinkRemoved {
+        before: usize,
+        after: usize,
+    },
+    /// The entry disappeared during `nb_snapshots` snapshots, and came back at `until`.
+    Glitch {
+        subject: Subject,
+        nb_snapshots: usize,
+        until: NaiveDateTime,
+    },
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::RouterAdded => "router-added",
+            EventKind::RouterRemoved => "router-removed",
+            EventKind::PeeringAppeared => "peering-appeared",
+            EventKind::PeeringDisappeared => "peering-disappeared",
+            EventKind::AdjacencyAdded => "adjacency-added",
+            EventKind::AdjacencyRemoved => "adjacency-removed",
+            EventKind::LinkAdded { .. } => "link-added",
+            EventKind::LinkRemoved { .. } => "link-removed",
+            EventKind::Glitch { .. } => "glitch",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            EventKind::LinkAdded { before, after } | EventKind::LinkRemoved { before, after } => {
+                format!("{} -> {} links", before, after)
+            }
+            EventKind::Glitch {
+                subject,
+                nb_snapshots,
+                until,
+            } => format!(
+                "{} missing in {} snapshots, back at {}",
+                subject.name(),
+                nb_snapshots,
+                until
+            ),
+            _ => String::new(),
+        }
+    }
+}
+
+/// What a glitch is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subject {
+    Router,
+    Peering,
+    Adjacency,
+    Links,
+}
+
+impl Subject {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Subject::Router => "router",
+            Subject::Peering => "peering",
+            Subject::Adjacency => "adjacency",
+            Subject::Links => "links",
+        }
+    }
+}
+
+/// One change of the topology, observed at `timestamp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub timestamp: NaiveDateTime,
+    pub kind: EventKind,
+    pub router: String,
+    pub peer: Option<String>,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.timestamp, self.kind.name(), self.router)?;
+        if let Some(peer) = &self.peer {
+            write!(f, " -> {}", peer)?;
+        }
+        let detail = self.kind.detail();
+        if !detail.is_empty() {
+            write!(f, " ({})", detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// A removal that is only reported if the entry does not come back in time.
+#[derive(Debug)]
+struct Pending {
+    subject: Subject,
+    /// Index of the first snapshot without the entry.
+    since: usize,
+    /// Number of links before the removal, for bundles.
+    nb_links: usize,
+    event: Event,
+}
+
+impl Pending {
+    fn matches(&self, subject: Subject, router: &str, peer: Option<&str>) -> bool {
+        self.subject == subject && self.event.router == router && self.event.peer.as_deref() == peer
+    }
+}
+
+/// Builds the event log from snapshots pushed in timestamp order.
+#[derive(Debug)]
+pub struct EventLog {
+    /// Maximum number of consecutive snapshots an entry may miss to be a glitch.
+    debounce: usize,
+    previous: Option<OvhData>,
+    nb_snapshots: usize,
+    pending: Vec<Pending>,
+    events: Vec<Event>,
+}
+
+impl EventLog {
+    /// Entries missing in at most `debounce` consecutive snapshots are reported as glitches.
+    pub fn new(debounce: usize) -> EventLog {
+        EventLog {
+            debounce,
+            previous: None,
+            nb_snapshots: 0,
+            pending: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Compares `data` to the previous snapshot. Only the last snapshot is kept.
+    pub fn push(&mut self, data: OvhData) {
+        let idx = self.nb_snapshots;
+        self.nb_snapshots += 1;
+        let previous = match self.previous.replace(data) {
+            Some(previous) => previous,
+            None => return,
+        };
+        let current = self.previous.as_ref().unwrap();
+        // Loads do not matter here
+        let diff = previous.diff_with(current, u32::MAX);
+        let timestamp = diff.to;
+
+        // Adjacencies of routers that (dis)appear go with them.
+        // External peers only matter through their peering sessions.
+        let routers_changed = diff
+            .changes
+            .iter()
+            .filter_map(|change| match change {
+                Change::RouterAdded { router } | Change::RouterRemoved { router } => {
+                    Some(router.to_string())
+                }
+                _ => None,
+            })
+            .filter(|router| !is_peer_from_name(router))
+            .collect::<HashSet<String>>();
+
+        for change in diff.changes {
+            match change {
+                Change::RouterAdded { router } | Change::RouterRemoved { router }
+                    if is_peer_from_name(&router) => {}
+                Change::RouterAdded { router } => {
+                    self.added(idx, timestamp, Subject::Router, router, None, (0, 0))
+                }
+                Change::RouterRemoved { router } => {
+                    self.removed(idx, timestamp, Subject::Router, router, None, (0, 0))
+                }
+                Change::PeerAdded { router, peer, .. } => {
+                    if let Some(subject) = adjacency_subject(&router, &peer, &routers_changed) {
+                        self.added(idx, timestamp, subject, router, Some(peer), (0, 0))
+                    }
+                }
+                Change::PeerRemoved { router, peer, .. } => {
+                    if let Some(subject) = adjacency_subject(&router, &peer, &routers_changed) {
+                        self.removed(idx, timestamp, subject, router, Some(peer), (0, 0))
+                    }
+                }
+                Change::EcmpSizeChanged {
+                    router,
+                    peer,
+                    before,
+                    after,
+                } => {
+                    if adjacency_subject(&router, &peer, &routers_changed).is_none() {
+                        continue;
+                    }
+                    let links = (before, after);
+                    if after > before {
+                        self.added(idx, timestamp, Subject::Links, router, Some(peer), links)
+                    } else {
+                        self.removed(idx, timestamp, Subject::Links, router, Some(peer), links)
+                    }
+                }
+                Change::LabelsChanged { .. } | Change::LoadChanged { .. } => {}
+            }
+        }
+
+        // Removals that lasted longer than the debounce window are real
+        let debounce = self.debounce;
+        let (expired, pending): (Vec<Pending>, Vec<Pending>) = self
+            .pending
+            .drain(..)
+            .partition(|p| idx + 1 - p.since > debounce);
+        self.pending = pending;
+        self.events.extend(expired.into_iter().map(|p| p.event));
+    }
+
+    fn added(
+        &mut self,
+        idx: usize,
+        timestamp: NaiveDateTime,
+        subject: Subject,
+        router: String,
+        peer: Option<String>,
+        (before, after): (usize, usize),
+    ) {
+        let pending = self
+            .pending
+            .iter()
+            .position(|p| p.matches(subject, &router, peer.as_deref()));
+        if let Some(pos) = pending {
+            let pending = self.pending.remove(pos);
+            if subject != Subject::Links || after >= pending.nb_links {
+                self.events.push(Event {
+                    timestamp: pending.event.timestamp,
+                    kind: EventKind::Glitch {
+                        subject,
+                        nb_snapshots: idx - pending.since,
+                        until: timestamp,
+                    },
+                    router,
+                    peer,
+                });
+                return;
+            }
+            // Only some of the links came back
+            self.events.push(pending.event);
+        }
+
+        let kind = match subject {
+            Subject::Router => EventKind::RouterAdded,
+            Subject::Peering => EventKind::PeeringAppeared,
+            Subject::Adjacency => EventKind::AdjacencyAdded,
+            Subject::Links => EventKind::LinkAdded { before, after },
+        };
+        self.events.push(Event {
+            timestamp,
+            kind,
+            router,
+            peer,
+        });
+    }
+
+    fn removed(
+        &mut self,
+        idx: usize,
+        timestamp: NaiveDateTime,
+        subject: Subject,
+        router: String,
+        peer: Option<String>,
+        (before, after): (usize, usize),
+    ) {
+        let kind = match subject {
+            Subject::Router => EventKind::RouterRemoved,
+            Subject::Peering => EventKind::PeeringDisappeared,
+            Subject::Adjacency => EventKind::AdjacencyRemoved,
+            Subject::Links => {
+                // A bundle shrinking again before the first removal is confirmed
+                if let Some(pending) = self
+                    .pending
+                    .iter_mut()
+                    .find(|p| p.matches(subject, &router, peer.as_deref()))
+                {
+                    pending.event.kind = EventKind::LinkRemoved {
+                        before: pending.nb_links,
+                        after,
+                    };
+                    return;
+                }
+                EventKind::LinkRemoved { before, after }
+            }
+        };
+        self.pending.push(Pending {
+            subject,
+            since: idx,
+            nb_links: before,
+            event: Event {
+                timestamp,
+                kind,
+                router,
+                peer,
+            },
+        });
+    }
+
+    /// Number of snapshots pushed so far.
+    pub fn nb_snapshots(&self) -> usize {
+        self.nb_snapshots
+    }
+
+    /// All the events, in chronological order. Removals still within the debounce window
+    /// at the end of the data are reported as removals.
+    pub fn finish(mut self) -> Vec<Event> {
+        self.events
+            .extend(self.pending.drain(..).map(|pending| pending.event));
+        self.events.sort_by_key(|event| event.timestamp);
+        self.events
+    }
+}
+
+/// Kind of adjacency between `router` and `peer`, if it should be reported from this side.
+fn adjacency_subject(
+    router: &str,
+    peer: &str,
+    routers_changed: &HashSet<String>,
+) -> Option<Subject> {
+    if routers_changed.contains(router) || routers_changed.contains(peer) {
+        return None;
+    }
+    match (is_peer_from_name(router), is_peer_from_name(peer)) {
+        (false, true) => Some(Subject::Peering),
+        (false, false) if router < peer => Some(Subject::Adjacency),
+        _ => None,
+    }
+}
+
+/// Writes the events as `timestamp,event,router,peer,detail`, with headers.
+pub fn write_events_csv<W: Write>(events: &[Event], wrt: W) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record(["timestamp", "event", "router", "peer", "detail"])?;
+    for event in events {
+        wrt.write_record([
+            event.timestamp.and_utc().timestamp().to_string(),
+            event.kind.name().to_string(),
+            event.router.to_string(),
+            event.peer.clone().unwrap_or_default(),
+            event.kind.detail(),
+        ])?;
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Link, Router};
+    use chrono::DateTime;
+    use std::collections::HashMap;
+
+    /// Builds a snapshot from `(router, peer, nb_links)` adjacencies, in both directions.
+    fn data(t: i64, adjacencies: &[(&str, &str, usize)]) -> OvhData {
+        let mut data: HashMap<String, Router> = HashMap::new();
+        for &(a, b, nb_links) in adjacencies {
+            for (router, peer) in [(a, b), (b, a)] {
+                let links = (0..nb_links)
+                    .map(|i| Link {
+                        label: format!("#{}", i),
+                        load: 10,
+                    })
+                    .collect();
+                data.entry(router.to_string())
+                    .or_insert_with(|| Router {
+                        name: router.to_string(),
+                        peers: HashMap::new(),
+                    })
+                    .peers
+                    .insert(peer.to_string(), links);
+            }
+        }
+        OvhData {
+            timestamp: DateTime::from_timestamp(t, 0).unwrap().naive_utc(),
+            data,
+        }
+    }
+
+    #[test]
+    fn test_events_and_glitches() {
+        let mut log = EventLog::new(1);
+        log.push(data(0, &[("a", "b", 2), ("b", "c", 1)]));
+        // `c` flaps for one snapshot, a link is added to a-b
+        log.push(data(300, &[("a", "b", 3)]));
+        log.push(data(600, &[("a", "b", 3), ("b", "c", 1)]));
+        // A peering appears, and `c` really leaves
+        log.push(data(900, &[("a", "b", 3), ("a", "AMS-IX", 1)]));
+        log.push(data(1200, &[("a", "b", 3), ("a", "AMS-IX", 1)]));
+        let events = log.finish();
+
+        let summary = events
+            .iter()
+            .map(|e| {
+                (
+                    e.timestamp.and_utc().timestamp(),
+                    e.kind.name(),
+                    e.router.as_str(),
+                )
+            })
+            .collect::<Vec<(i64, &str, &str)>>();
+        assert_eq!(
+            summary,
+            vec![
+                (300, "link-added", "a"),
+                (300, "glitch", "c"),
+                (900, "peering-appeared", "a"),
+                (900, "router-removed", "c"),
+            ]
+        );
+        assert_eq!(
+            events[0].kind,
+            EventKind::LinkAdded {
+                before: 2,
+                after: 3
+            }
+        );
+    }
+}
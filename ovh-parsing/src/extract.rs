@@ -0,0 +1,262 @@
+//! Extraction of the load time series of the links between routers selected by name.
+//!
+//! Routers and peers are selected with glob patterns, where `*` matches any sequence of
+//! characters and `?` any single character. The loads of each selected adjacency are
+//! given in both directions: `up` for the links listed by the router towards the peer,
+//! `down` for the links listed by the peer towards the router.
+
+use crate::load_store::{Direction, LinkKey, LoadStore};
+use chrono::NaiveDateTime;
+use csv::Writer;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::ops::RangeBounds;
+use std::str::FromStr;
+
+/// Glob pattern on router names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamePattern(String);
+
+impl NamePattern {
+    pub fn matches(&self, name: &str) -> bool {
+        let (pattern, name) = (self.0.as_bytes(), name.as_bytes());
+        let (mut p, mut n) = (0, 0);
+        // Position of the last `*` in the pattern, and where it started to match in the name
+        let mut backtrack: Option<(usize, usize)> = None;
+        while n < name.len() {
+            match pattern.get(p) {
+                Some(b'*') => {
+                    backtrack = Some((p, n));
+                    p += 1;
+                }
+                Some(&c) if c == b'?' || c == name[n] => {
+                    p += 1;
+                    n += 1;
+                }
+                _ => match backtrack {
+                    // The last `*` absorbs one more character
+                    Some((star, start)) => {
+                        backtrack = Some((star, start + 1));
+                        p = star + 1;
+                        n = start + 1;
+                    }
+                    None => return false,
+                },
+            }
+        }
+        pattern[p..].iter().all(|&c| c == b'*')
+    }
+}
+
+impl FromStr for NamePattern {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<NamePattern, Infallible> {
+        Ok(NamePattern(s.to_string()))
+    }
+}
+
+impl fmt::Display for NamePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Loads of the links between `router` and `peer`, aligned on the timestamps of the snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadTable {
+    pub router: String,
+    pub peer: String,
+    pub timestamps: Vec<NaiveDateTime>,
+    /// Direction, label and loads of each link. A load is `None` when the link is
+    /// absent from the snapshot.
+    pub rows: Vec<(Direction, String, Vec<Option<u32>>)>,
+}
+
+impl LoadTable {
+    /// Writes the table as `direction,label,<t1>,<t2>...` with the unix timestamps as headers,
+    /// one row per link, and `-1` for the snapshots where the link is absent.
+    pub fn write_wide_csv<W: Write>(&self, wrt: W) -> Result<(), Box<dyn Error>> {
+        let mut wrt = Writer::from_writer(wrt);
+        let mut headers = vec!["direction".to_string(), "label".to_string()];
+        headers.extend(
+            self.timestamps
+                .iter()
+                .map(|t| t.and_utc().timestamp().to_string()),
+        );
+        wrt.write_record(&headers)?;
+        for (direction, label, loads) in self.rows.iter() {
+            let mut record = vec![direction.as_str().to_string(), label.to_string()];
+            record.extend(loads.iter().map(|load| match load {
+                Some(load) => load.to_string(),
+                None => "-1".to_string(),
+            }));
+            wrt.write_record(&record)?;
+        }
+        wrt.flush()?;
+        Ok(())
+    }
+}
+
+/// Extracts the loads of the links between the routers matching `router` and the peers
+/// matching `peer`, within `range`, as one table per adjacency. When both ends of an
+/// adjacency match both patterns, it is only given once, from the lowest router name.
+pub fn extract_loads<R: RangeBounds<NaiveDateTime> + Clone>(
+    store: &LoadStore,
+    router: &NamePattern,
+    peer: &NamePattern,
+    range: R,
+) -> Vec<LoadTable> {
+    let timestamps = store
+        .timestamps()
+        .into_iter()
+        .filter(|t| range.contains(t))
+        .collect::<Vec<NaiveDateTime>>();
+
+    let mut adjacencies: BTreeMap<(String, String), Vec<LinkKey>> = BTreeMap::new();
+    for key in store
+        .keys()
+        .filter(|key| router.matches(&key.router) && peer.matches(&key.peer))
+    {
+        adjacencies
+            .entry((key.router.to_string(), key.peer.to_string()))
+            .or_default()
+            .push(key);
+    }
+    let pairs = adjacencies
+        .keys()
+        .cloned()
+        .collect::<BTreeSet<(String, String)>>();
+
+    let mut tables = Vec::new();
+    for ((router, peer), mut keys) in adjacencies {
+        if peer < router && pairs.contains(&(peer.to_string(), router.to_string())) {
+            continue;
+        }
+        keys.sort_by(|a, b| (a.direction, &a.label).cmp(&(b.direction, &b.label)));
+        let rows = keys
+            .into_iter()
+            .map(|key| {
+                let mut loads = vec![None; timestamps.len()];
+                for (t, load) in store.link(&key, range.clone()).unwrap_or_default() {
+                    if let Ok(idx) = timestamps.binary_search(&t) {
+                        loads[idx] = Some(load);
+                    }
+                }
+                (key.direction, key.label, loads)
+            })
+            .filter(|(_, _, loads)| loads.iter().any(Option::is_some))
+            .collect::<Vec<(Direction, String, Vec<Option<u32>>)>>();
+        if !rows.is_empty() {
+            tables.push(LoadTable {
+                router,
+                peer,
+                timestamps: timestamps.clone(),
+                rows,
+            });
+        }
+    }
+    tables
+}
+
+/// Writes the tables as `timestamp,router,peer,direction,label,load`, with headers,
+/// one row per link and snapshot where the link is present.
+pub fn write_long_csv<W: Write>(tables: &[LoadTable], wrt: W) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record(["timestamp", "router", "peer", "direction", "label", "load"])?;
+    for table in tables {
+        for (direction, label, loads) in table.rows.iter() {
+            for (t, load) in table.timestamps.iter().zip(loads) {
+                if let Some(load) = load {
+                    wrt.write_record([
+                        t.and_utc().timestamp().to_string(),
+                        table.router.to_string(),
+                        table.peer.to_string(),
+                        direction.as_str().to_string(),
+                        label.to_string(),
+                        load.to_string(),
+                    ])?;
+                }
+            }
+        }
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Link, OvhData, Router};
+    use chrono::DateTime;
+    use std::collections::HashMap;
+
+    fn snapshot(t: i64, links: &[(&str, &str, u32)]) -> OvhData {
+        let mut data: HashMap<String, Router> = HashMap::new();
+        for &(router, peer, load) in links {
+            data.entry(router.to_string())
+                .or_insert_with(|| Router {
+                    name: router.to_string(),
+                    peers: HashMap::new(),
+                })
+                .peers
+                .entry(peer.to_string())
+                .or_default()
+                .push(Link {
+                    label: "#1".to_string(),
+                    load,
+                });
+        }
+        OvhData {
+            timestamp: DateTime::from_timestamp(t, 0).unwrap().naive_utc(),
+            data,
+        }
+    }
+
+    #[test]
+    fn test_extract() {
+        let pattern = |s: &str| s.parse::<NamePattern>().unwrap();
+        assert!(pattern("ams-*-n7").matches("ams-1-n7"));
+        assert!(pattern("*IX").matches("AMS-IX"));
+        assert!(pattern("a?c*").matches("abc"));
+        assert!(!pattern("ams-*-n7").matches("ams-1-n6"));
+        assert!(!pattern("a?c").matches("ac"));
+
+        let mut store = LoadStore::new();
+        store.push(&snapshot(100, &[("ams-1", "IX", 10), ("IX", "ams-1", 20)]));
+        store.push(&snapshot(200, &[("ams-1", "IX", 15), ("ams-2", "IX", 1)]));
+        store.push(&snapshot(300, &[("ams-1", "IX", 5), ("IX", "ams-1", 25)]));
+
+        let tables = extract_loads(&store, &pattern("ams-1"), &pattern("IX"), ..);
+        assert_eq!(tables.len(), 1);
+        let mut wide = Vec::new();
+        tables[0].write_wide_csv(&mut wide).unwrap();
+        assert_eq!(
+            String::from_utf8(wide).unwrap(),
+            "direction,label,100,200,300\nup,#1,10,15,5\ndown,#1,20,-1,25\n"
+        );
+
+        // Each adjacency is given once, even if both ends match both patterns
+        let tables = extract_loads(&store, &pattern("*"), &pattern("*"), ..);
+        assert_eq!(
+            tables
+                .iter()
+                .map(|t| (t.router.as_str(), t.peer.as_str()))
+                .collect::<Vec<(&str, &str)>>(),
+            vec![("IX", "ams-1"), ("IX", "ams-2")]
+        );
+        let start = DateTime::from_timestamp(150, 0).unwrap().naive_utc();
+        let tables = extract_loads(&store, &pattern("ams-*"), &pattern("IX"), start..);
+        let mut long = Vec::new();
+        write_long_csv(&tables, &mut long).unwrap();
+        assert_eq!(
+            String::from_utf8(long).unwrap(),
+            "timestamp,router,peer,direction,label,load\n\
+             200,ams-1,IX,up,#1,15\n300,ams-1,IX,up,#1,5\n300,ams-1,IX,down,#1,25\n\
+             200,ams-2,IX,up,#1,1\n"
+        );
+    }
+}
@@ -0,0 +1,747 @@
+//! Graph view of a snapshot, for path and centrality computations.
+//!
+//! Routers, and the peers they mention, are the nodes of an undirected graph indexed by
+//! `NodeId`. All the parallel links between two routers form a single edge, which keeps
+//! the links as listed by each end, hence their number and loads.
+
+use crate::{is_peer_from_name, Link, OvhData, OvhNodeFilter};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+pub type NodeId = usize;
+pub type EdgeId = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub name: String,
+    pub external: bool,
+}
+
+/// All the parallel links between the routers `a` and `b`.
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub a: NodeId,
+    pub b: NodeId,
+    /// Links listed by `a` towards `b`.
+    pub links_ab: Vec<Link>,
+    /// Links listed by `b` towards `a`.
+    pub links_ba: Vec<Link>,
+}
+
+impl Edge {
+    /// Number of parallel links, the largest of both directions.
+    pub fn nb_links(&self) -> usize {
+        self.links_ab.len().max(self.links_ba.len())
+    }
+
+    /// The other end of the edge.
+    pub fn other(&self, node: NodeId) -> NodeId {
+        if node == self.a {
+            self.b
+        } else {
+            self.a
+        }
+    }
+
+    /// Links listed by `node` towards the other end.
+    pub fn links_from(&self, node: NodeId) -> &[Link] {
+        if node == self.a {
+            &self.links_ab
+        } else {
+            &self.links_ba
+        }
+    }
+
+    /// Highest load of the links, in both directions.
+    pub fn max_load(&self) -> u32 {
+        self.links_ab
+            .iter()
+            .chain(self.links_ba.iter())
+            .map(|l| l.load)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// Distances from a source, with all the shortest paths.
+#[derive(Debug, Clone)]
+pub struct ShortestPaths {
+    pub source: NodeId,
+    /// Distance of each node, `None` if unreachable.
+    pub distances: Vec<Option<f64>>,
+    /// Predecessors of each node on all its shortest paths, with the edge used.
+    pub predecessors: Vec<Vec<(NodeId, EdgeId)>>,
+}
+
+impl ShortestPaths {
+    /// One of the shortest paths towards `target`, from the source to `target` included.
+    pub fn path_to(&self, target: NodeId) -> Option<Vec<NodeId>> {
+        self.distances[target]?;
+        let mut path = vec![target];
+        let mut node = target;
+        while node != self.source {
+            node = self.predecessors[node][0].0;
+            path.push(node);
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// Entry of the Dijkstra priority queue, the closest node first.
+#[derive(Debug, PartialEq)]
+struct Candidate(f64, NodeId);
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0).then(other.1.cmp(&self.1))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    indexes: HashMap<String, NodeId>,
+    /// Neighbours of each node, with the edge leading to them.
+    adjacency: Vec<Vec<(NodeId, EdgeId)>>,
+}
+
+impl Graph {
+    /// Graph of all the routers and peers of the snapshot.
+    pub fn from_data(data: &OvhData) -> Graph {
+        Graph::from_data_with(data, OvhNodeFilter::All)
+    }
+
+    /// Graph of the routers selected by `ovh_nodes`, with the links between them.
+    pub fn from_data_with(data: &OvhData, ovh_nodes: OvhNodeFilter) -> Graph {
+        let keep = |name: &str| match ovh_nodes {
+            OvhNodeFilter::All => true,
+            OvhNodeFilter::Ovh => !is_peer_from_name(name),
+            OvhNodeFilter::External => is_peer_from_name(name),
+        };
+        // Sorted names for stable indexes
+        let mut names = data.data.keys().collect::<Vec<&String>>();
+        names.sort();
+
+        let mut graph = Graph::default();
+        let mut edges: HashMap<(NodeId, NodeId), EdgeId> = HashMap::new();
+        for name in names.into_iter().filter(|name| keep(name)) {
+            let router = graph.add_node(name);
+            let mut peers = data.data[name].peers.iter().collect::<Vec<_>>();
+            peers.sort_by_key(|(peer, _)| *peer);
+            for (peer_name, links) in peers.into_iter().filter(|(peer, _)| keep(peer)) {
+                let peer = graph.add_node(peer_name);
+                if peer == router {
+                    continue;
+                }
+                let id = *edges
+                    .entry((router.min(peer), router.max(peer)))
+                    .or_insert_with(|| {
+                        graph.edges.push(Edge {
+                            a: router,
+                            b: peer,
+                            links_ab: Vec::new(),
+                            links_ba: Vec::new(),
+                        });
+                        let id = graph.edges.len() - 1;
+                        graph.adjacency[router].push((peer, id));
+                        graph.adjacency[peer].push((router, id));
+                        id
+                    });
+                let edge = &mut graph.edges[id];
+                if edge.a == router {
+                    edge.links_ab.extend(links.iter().cloned());
+                } else {
+                    edge.links_ba.extend(links.iter().cloned());
+                }
+            }
+        }
+        graph
+    }
+
+    fn add_node(&mut self, name: &str) -> NodeId {
+        if let Some(&id) = self.indexes.get(name) {
+            return id;
+        }
+        self.nodes.push(Node {
+            name: name.to_string(),
+            external: is_peer_from_name(name),
+        });
+        self.adjacency.push(Vec::new());
+        self.indexes.insert(name.to_string(), self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    pub fn nb_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn nb_edges(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn edge(&self, id: EdgeId) -> &Edge {
+        &self.edges[id]
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<NodeId> {
+        self.indexes.get(name).copied()
+    }
+
+    /// Neighbours of `node`, with the edge leading to each of them.
+    pub fn neighbors(&self, node: NodeId) -> &[(NodeId, EdgeId)] {
+        &self.adjacency[node]
+    }
+
+    pub fn edge_between(&self, a: NodeId, b: NodeId) -> Option<EdgeId> {
+        self.adjacency[a]
+            .iter()
+            .find(|(neighbor, _)| *neighbor == b)
+            .map(|(_, edge)| *edge)
+    }
+
+    pub fn degree(&self, node: NodeId) -> usize {
+        self.adjacency[node].len()
+    }
+
+    /// Number of hops from `source` to each node, `None` if unreachable.
+    pub fn bfs(&self, source: NodeId) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.nb_nodes()];
+        distances[source] = Some(0);
+        let mut queue = VecDeque::from([source]);
+        while let Some(node) = queue.pop_front() {
+            let next = distances[node].map(|d| d + 1);
+            for &(neighbor, _) in self.adjacency[node].iter() {
+                if distances[neighbor].is_none() {
+                    distances[neighbor] = next;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        distances
+    }
+
+    /// Number of hops from the closest of the `sources` to each node, `None` if unreachable.
+    pub fn bfs_from(&self, sources: &[NodeId]) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.nb_nodes()];
+        sources.iter().for_each(|&s| distances[s] = Some(0));
+        let mut queue = sources.iter().copied().collect::<VecDeque<NodeId>>();
+        while let Some(node) = queue.pop_front() {
+            let next = distances[node].map(|d| d + 1);
+            for &(neighbor, _) in self.adjacency[node].iter() {
+                if distances[neighbor].is_none() {
+                    distances[neighbor] = next;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        distances
+    }
+
+    /// Share of the traffic sent from the `sources` to the `targets` that crosses each edge,
+    /// with the node it leaves from, when it follows the shortest paths (in hops) and is split
+    /// equally between the next hops (ECMP). The traffic leaves from the sources closest to
+    /// the targets, in equal parts. The paths only go through the nodes accepted by `transit`
+    /// (the sources and targets excepted). Empty if no target can be reached.
+    pub fn ecmp_fractions<F>(
+        &self,
+        sources: &[NodeId],
+        targets: &[NodeId],
+        transit: F,
+    ) -> Vec<(NodeId, EdgeId, f64)>
+    where
+        F: Fn(NodeId) -> bool,
+    {
+        let distances = self.bfs_through(targets, &transit);
+        let closest = match sources.iter().filter_map(|&s| distances[s]).min() {
+            Some(distance) if distance > 0 => distance,
+            _ => return Vec::new(),
+        };
+        let starts = sources
+            .iter()
+            .copied()
+            .filter(|&s| distances[s] == Some(closest))
+            .collect::<Vec<NodeId>>();
+
+        // Pushes the flow hop by hop towards the targets, the farthest nodes first
+        let mut fractions = Vec::new();
+        let mut flows: HashMap<NodeId, f64> = starts
+            .iter()
+            .map(|&s| (s, 1.0 / starts.len() as f64))
+            .collect();
+        let mut order = starts;
+        while let Some(node) = order.pop() {
+            let (flow, distance) = match (flows.remove(&node), distances[node]) {
+                (Some(flow), Some(distance)) if distance > 0 => (flow, distance),
+                _ => continue,
+            };
+            let next_hops = self.adjacency[node]
+                .iter()
+                .filter(|&&(neighbor, _)| {
+                    distances[neighbor] == Some(distance - 1)
+                        && (distance == 1 || transit(neighbor))
+                })
+                .collect::<Vec<&(NodeId, EdgeId)>>();
+            for &(next, edge) in next_hops.iter().copied() {
+                let share = flow / next_hops.len() as f64;
+                fractions.push((node, edge, share));
+                if !flows.contains_key(&next) {
+                    order.push(next);
+                }
+                *flows.entry(next).or_default() += share;
+            }
+            // Nodes closer to the targets come last, once all their flow is known
+            order.sort_by_key(|&n| distances[n]);
+        }
+        fractions
+    }
+
+    /// Same as `bfs_from`, but the paths only go through the nodes accepted by `transit`:
+    /// the other nodes get their distance, but are not expanded (unless they are sources).
+    pub fn bfs_through<F>(&self, sources: &[NodeId], transit: F) -> Vec<Option<usize>>
+    where
+        F: Fn(NodeId) -> bool,
+    {
+        let mut distances = vec![None; self.nb_nodes()];
+        sources.iter().for_each(|&s| distances[s] = Some(0));
+        let mut queue = sources.iter().copied().collect::<VecDeque<NodeId>>();
+        while let Some(node) = queue.pop_front() {
+            if distances[node] != Some(0) && !transit(node) {
+                continue;
+            }
+            let next = distances[node].map(|d| d + 1);
+            for &(neighbor, _) in self.adjacency[node].iter() {
+                if distances[neighbor].is_none() {
+                    distances[neighbor] = next;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        distances
+    }
+
+    /// Shortest paths from `source` with the (non-negative) `weight` of each edge.
+    pub fn dijkstra<F>(&self, source: NodeId, weight: F) -> ShortestPaths
+    where
+        F: Fn(&Edge) -> f64,
+    {
+        let mut distances: Vec<Option<f64>> = vec![None; self.nb_nodes()];
+        let mut predecessors = vec![Vec::new(); self.nb_nodes()];
+        let mut done = vec![false; self.nb_nodes()];
+        distances[source] = Some(0.0);
+        let mut heap = BinaryHeap::from([Candidate(0.0, source)]);
+        while let Some(Candidate(distance, node)) = heap.pop() {
+            if done[node] {
+                continue;
+            }
+            done[node] = true;
+            for &(neighbor, edge) in self.adjacency[node].iter() {
+                let candidate = distance + weight(&self.edges[edge]);
+                match distances[neighbor] {
+                    Some(d) if candidate > d => {}
+                    Some(d) if candidate == d => predecessors[neighbor].push((node, edge)),
+                    _ => {
+                        distances[neighbor] = Some(candidate);
+                        predecessors[neighbor] = vec![(node, edge)];
+                        heap.push(Candidate(candidate, neighbor));
+                    }
+                }
+            }
+        }
+        ShortestPaths {
+            source,
+            distances,
+            predecessors,
+        }
+    }
+
+    /// One of the paths with the fewest hops from `from` to `to`.
+    pub fn shortest_path(&self, from: NodeId, to: NodeId) -> Option<Vec<NodeId>> {
+        self.dijkstra(from, |_| 1.0).path_to(to)
+    }
+
+    /// Largest number of hops between two connected nodes, `None` for an empty graph.
+    pub fn diameter(&self) -> Option<usize> {
+        (0..self.nb_nodes())
+            .filter_map(|node| self.bfs(node).into_iter().flatten().max())
+            .max()
+    }
+
+    /// Connected components, the largest first.
+    pub fn components(&self) -> Vec<Vec<NodeId>> {
+        let mut seen = vec![false; self.nb_nodes()];
+        let mut components = Vec::new();
+        for node in 0..self.nb_nodes() {
+            if seen[node] {
+                continue;
+            }
+            let component = self
+                .bfs(node)
+                .into_iter()
+                .enumerate()
+                .filter(|(_, d)| d.is_some())
+                .map(|(n, _)| n)
+                .collect::<Vec<NodeId>>();
+            component.iter().for_each(|&n| seen[n] = true);
+            components.push(component);
+        }
+        components.sort_by_key(|c| std::cmp::Reverse(c.len()));
+        components
+    }
+
+    /// Degree of each node, divided by the number of other nodes.
+    pub fn degree_centrality(&self) -> Vec<f64> {
+        let others = (self.nb_nodes().max(2) - 1) as f64;
+        (0..self.nb_nodes())
+            .map(|node| self.degree(node) as f64 / others)
+            .collect()
+    }
+
+    /// Closeness of each node: the inverse of its mean distance in hops to the nodes it
+    /// reaches, scaled by the fraction of the nodes it reaches (Wasserman and Faust).
+    pub fn closeness_centrality(&self) -> Vec<f64> {
+        let others = (self.nb_nodes().max(2) - 1) as f64;
+        (0..self.nb_nodes())
+            .map(|node| {
+                let distances = self.bfs(node).into_iter().flatten().collect::<Vec<usize>>();
+                let total = distances.iter().sum::<usize>() as f64;
+                let reached = (distances.len() - 1) as f64;
+                if total == 0.0 {
+                    0.0
+                } else {
+                    (reached / total) * (reached / others)
+                }
+            })
+            .collect()
+    }
+
+    /// Fraction of the shortest paths (in hops) between other nodes going through each node,
+    /// computed with Brandes' algorithm.
+    pub fn betweenness_centrality(&self) -> Vec<f64> {
+        let n = self.nb_nodes();
+        let mut centrality = vec![0.0; n];
+        for source in 0..n {
+            let mut order = Vec::with_capacity(n);
+            let mut predecessors: Vec<Vec<NodeId>> = vec![Vec::new(); n];
+            let mut nb_paths = vec![0.0; n];
+            let mut distances: Vec<Option<usize>> = vec![None; n];
+            nb_paths[source] = 1.0;
+            distances[source] = Some(0);
+            let mut queue = VecDeque::from([source]);
+            while let Some(node) = queue.pop_front() {
+                order.push(node);
+                let next = distances[node].unwrap() + 1;
+                for &(neighbor, _) in self.adjacency[node].iter() {
+                    if distances[neighbor].is_none() {
+                        distances[neighbor] = Some(next);
+                        queue.push_back(neighbor);
+                    }
+                    if distances[neighbor] == Some(next) {
+                        nb_paths[neighbor] += nb_paths[node];
+                        predecessors[neighbor].push(node);
+                    }
+                }
+            }
+            let mut dependency = vec![0.0; n];
+            for &node in order.iter().rev() {
+                for &pred in predecessors[node].iter() {
+                    dependency[pred] += nb_paths[pred] / nb_paths[node] * (1.0 + dependency[node]);
+                }
+                if node != source {
+                    centrality[node] += dependency[node];
+                }
+            }
+        }
+        // Each pair is counted from both ends
+        let scale = if n > 2 {
+            1.0 / ((n - 1) * (n - 2)) as f64
+        } else {
+            0.0
+        };
+        centrality.iter().map(|c| c * scale).collect()
+    }
+
+    /// Nodes and edges whose removal disconnects their component (Tarjan's algorithm).
+    fn cut_elements(&self) -> (Vec<NodeId>, Vec<EdgeId>) {
+        let n = self.nb_nodes();
+        let mut discovery: Vec<Option<usize>> = vec![None; n];
+        let mut low = vec![0; n];
+        let mut articulation = vec![false; n];
+        let mut bridges = Vec::new();
+        let mut time = 0;
+        for root in 0..n {
+            if discovery[root].is_some() {
+                continue;
+            }
+            discovery[root] = Some(time);
+            low[root] = time;
+            time += 1;
+            let mut root_children = 0;
+            // Node, edge from its parent, and next neighbour to visit
+            let mut stack: Vec<(NodeId, Option<EdgeId>, usize)> = vec![(root, None, 0)];
+            while let Some(&(node, parent_edge, next)) = stack.last() {
+                if let Some(&(neighbor, edge)) = self.adjacency[node].get(next) {
+                    stack.last_mut().unwrap().2 += 1;
+                    if Some(edge) == parent_edge {
+                        continue;
+                    }
+                    match discovery[neighbor] {
+                        Some(d) => low[node] = low[node].min(d),
+                        None => {
+                            discovery[neighbor] = Some(time);
+                            low[neighbor] = time;
+                            time += 1;
+                            if node == root {
+                                root_children += 1;
+                            }
+                            stack.push((neighbor, Some(edge), 0));
+                        }
+                    }
+                    continue;
+                }
+                stack.pop();
+                if let Some(&(parent, _, _)) = stack.last() {
+                    low[parent] = low[parent].min(low[node]);
+                    let parent_discovery = discovery[parent].unwrap();
+                    if low[node] > parent_discovery {
+                        bridges.push(parent_edge.unwrap());
+                    }
+                    if parent != root && low[node] >= parent_discovery {
+                        articulation[parent] = true;
+                    }
+                }
+            }
+            if root_children > 1 {
+                articulation[root] = true;
+            }
+        }
+        bridges.sort();
+        let articulation = (0..n).filter(|&node| articulation[node]).collect();
+        (articulation, bridges)
+    }
+
+    /// Nodes whose removal disconnects their component.
+    pub fn articulation_points(&self) -> Vec<NodeId> {
+        self.cut_elements().0
+    }
+
+    /// Edges whose removal disconnects their component. Parallel links count as one edge.
+    pub fn bridges(&self) -> Vec<EdgeId> {
+        self.cut_elements().1
+    }
+
+    /// Minimum cut separating all the `sources` from all the `sinks`, where each edge can
+    /// carry `capacity(edge)` in both directions, with the edges of one such cut.
+    /// `None` if a node is both a source and a sink.
+    pub fn min_edge_cut<F>(
+        &self,
+        sources: &[NodeId],
+        sinks: &[NodeId],
+        capacity: F,
+    ) -> Option<(usize, Vec<EdgeId>)>
+    where
+        F: Fn(&Edge) -> usize,
+    {
+        if sources.iter().any(|s| sinks.contains(s)) {
+            return None;
+        }
+        let capacities = self.edges.iter().map(capacity).collect::<Vec<usize>>();
+        // Flow on each edge, positive from `a` to `b`
+        let mut flows = vec![0i64; self.nb_edges()];
+        let residual = |flows: &[i64], from: NodeId, edge: EdgeId| {
+            let flow = match self.edges[edge].a == from {
+                true => flows[edge],
+                false => -flows[edge],
+            };
+            capacities[edge] as i64 - flow
+        };
+        // Edmonds-Karp: augments along the shortest paths of the residual graph
+        let mut total = 0;
+        loop {
+            let mut predecessors: Vec<Option<(NodeId, EdgeId)>> = vec![None; self.nb_nodes()];
+            let mut seen = vec![false; self.nb_nodes()];
+            let mut queue = sources.iter().copied().collect::<VecDeque<NodeId>>();
+            sources.iter().for_each(|&s| seen[s] = true);
+            let mut reached = None;
+            while let Some(node) = queue.pop_front() {
+                if sinks.contains(&node) {
+                    reached = Some(node);
+                    break;
+                }
+                for &(neighbor, edge) in self.adjacency[node].iter() {
+                    if !seen[neighbor] && residual(&flows, node, edge) > 0 {
+                        seen[neighbor] = true;
+                        predecessors[neighbor] = Some((node, edge));
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+            let Some(sink) = reached else {
+                let mut cut = (0..self.nb_edges())
+                    .filter(|&e| seen[self.edges[e].a] != seen[self.edges[e].b])
+                    .collect::<Vec<EdgeId>>();
+                cut.sort();
+                return Some((total, cut));
+            };
+            let mut path = Vec::new();
+            let mut node = sink;
+            while let Some((previous, edge)) = predecessors[node] {
+                path.push((previous, edge));
+                node = previous;
+            }
+            let bottleneck = path
+                .iter()
+                .map(|&(from, edge)| residual(&flows, from, edge))
+                .min()
+                .unwrap_or_default();
+            for (from, edge) in path {
+                match self.edges[edge].a == from {
+                    true => flows[edge] += bottleneck,
+                    false => flows[edge] -= bottleneck,
+                }
+            }
+            total += bottleneck as usize;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Router;
+    use chrono::DateTime;
+
+    /// Square a-b-c-d with a tail d-e-F, and two links between a and b.
+    fn data() -> OvhData {
+        let mut data: HashMap<String, Router> = HashMap::new();
+        for (router, peer, label) in [
+            ("a", "b", "#1"),
+            ("a", "b", "#2"),
+            ("b", "a", "#1"),
+            ("b", "c", "#1"),
+            ("c", "d", "#1"),
+            ("d", "a", "#1"),
+            ("d", "e", "#1"),
+            ("e", "FRANCE-IX", "#1"),
+        ] {
+            data.entry(router.to_string())
+                .or_insert_with(|| Router {
+                    name: router.to_string(),
+                    peers: HashMap::new(),
+                })
+                .peers
+                .entry(peer.to_string())
+                .or_default()
+                .push(Link {
+                    label: label.to_string(),
+                    load: 10,
+                });
+        }
+        OvhData {
+            timestamp: DateTime::from_timestamp(0, 0).unwrap().naive_utc(),
+            data,
+        }
+    }
+
+    #[test]
+    fn test_graph() {
+        let graph = Graph::from_data(&data());
+        let id = |name: &str| graph.index_of(name).unwrap();
+        let names = |ids: Vec<NodeId>| {
+            ids.into_iter()
+                .map(|i| graph.node(i).name.as_str())
+                .collect::<Vec<&str>>()
+        };
+        assert_eq!((graph.nb_nodes(), graph.nb_edges()), (6, 6));
+        assert!(graph.node(id("FRANCE-IX")).external);
+        let ab = graph.edge(graph.edge_between(id("a"), id("b")).unwrap());
+        assert_eq!((ab.nb_links(), ab.links_from(id("b")).len()), (2, 1));
+
+        assert_eq!(graph.bfs(id("a"))[id("FRANCE-IX")], Some(3));
+        assert_eq!(graph.diameter(), Some(4));
+        assert_eq!(graph.components().len(), 1);
+        let paths = graph.dijkstra(id("a"), |_| 1.0);
+        // Two shortest paths from a to c
+        assert_eq!(paths.predecessors[id("c")].len(), 2);
+        assert_eq!(
+            names(graph.shortest_path(id("b"), id("e")).unwrap()),
+            vec!["b", "a", "d", "e"]
+        );
+
+        assert_eq!(names(graph.articulation_points()), vec!["d", "e"]);
+        let bridges = graph
+            .bridges()
+            .into_iter()
+            .map(|e| {
+                let edge = graph.edge(e);
+                (
+                    graph.node(edge.a).name.as_str(),
+                    graph.node(edge.b).name.as_str(),
+                )
+            })
+            .collect::<Vec<(&str, &str)>>();
+        assert_eq!(bridges, vec![("d", "e"), ("e", "FRANCE-IX")]);
+
+        let betweenness = graph.betweenness_centrality();
+        assert_eq!(betweenness[id("FRANCE-IX")], 0.0);
+        // e is on the paths between FRANCE-IX and the 4 other nodes, out of 10 pairs
+        assert!((betweenness[id("e")] - 0.4).abs() < 1e-9);
+        let closeness = graph.closeness_centrality();
+        assert!(closeness[id("d")] > closeness[id("b")]);
+
+        let internal = Graph::from_data_with(&data(), OvhNodeFilter::Ovh);
+        assert_eq!((internal.nb_nodes(), internal.nb_edges()), (5, 5));
+    }
+
+    #[test]
+    fn test_min_edge_cut() {
+        let graph = Graph::from_data(&data());
+        let id = |name: &str| graph.index_of(name).unwrap();
+        let nb_links = |edge: &Edge| edge.nb_links();
+        let (cut, edges) = graph
+            .min_edge_cut(&[id("b")], &[id("e")], nb_links)
+            .unwrap();
+        assert_eq!(
+            (cut, edges),
+            (1, vec![graph.edge_between(id("d"), id("e")).unwrap()])
+        );
+        // The two links between a and b count: the cut is around d
+        let (cut, edges) = graph
+            .min_edge_cut(&[id("b")], &[id("d")], nb_links)
+            .unwrap();
+        assert_eq!((cut, edges.len()), (2, 2));
+        assert_eq!(graph.min_edge_cut(&[id("b")], &[id("b")], nb_links), None);
+    }
+
+    #[test]
+    fn test_bfs_through() {
+        let graph = Graph::from_data(&data());
+        let id = |name: &str| graph.index_of(name).unwrap();
+        // e is reached, but the paths cannot go through it
+        let distances = graph.bfs_through(&[id("FRANCE-IX")], |n| n != id("e"));
+        assert_eq!((distances[id("e")], distances[id("d")]), (Some(1), None));
+        let distances = graph.bfs_through(&[id("e")], |n| n != id("e"));
+        assert_eq!(distances[id("a")], Some(2));
+    }
+}
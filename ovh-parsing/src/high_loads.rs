@@ -0,0 +1,261 @@
+//! Detection of the high-load episodes of the links.
+//!
+//! A link is highly loaded in a snapshot when its load reaches the threshold. Consecutive
+//! high loads of a link belong to the same episode as long as they are at most `max_gap`
+//! apart, so that a short dip does not split an episode. The timeline of the snapshots
+//! tells when the data itself is missing: when no snapshot exists for more than `max_gap`,
+//! the ongoing episodes are closed and flagged as truncated, since their actual end is unknown.
+
+use crate::OvhData;
+use chrono::{Duration, NaiveDateTime};
+use csv::Writer;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+
+/// Consecutive high loads of the link `label` from `router` towards `peer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Episode {
+    pub router: String,
+    pub peer: String,
+    pub label: String,
+    /// First and last snapshots where the load reached the threshold.
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// Number of snapshots where the load reached the threshold.
+    pub nb_snapshots: usize,
+    pub peak: u32,
+    /// Mean of the high loads.
+    pub mean: f64,
+    /// The episode was interrupted by missing data or by the end of the data.
+    pub truncated: bool,
+}
+
+impl Episode {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+#[derive(Debug)]
+struct OpenEpisode {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    nb_snapshots: usize,
+    peak: u32,
+    sum: u64,
+}
+
+/// Finds the high-load episodes from snapshots pushed in timestamp order.
+#[derive(Debug)]
+pub struct HighLoadDetector {
+    threshold: u32,
+    max_gap: Duration,
+    last_snapshot: Option<NaiveDateTime>,
+    open: HashMap<(String, String, String), OpenEpisode>,
+    episodes: Vec<Episode>,
+}
+
+impl HighLoadDetector {
+    /// Loads of at least `threshold` (%) are high, and high loads at most `max_gap` apart
+    /// are merged in the same episode.
+    pub fn new(threshold: u32, max_gap: Duration) -> HighLoadDetector {
+        HighLoadDetector {
+            threshold,
+            max_gap,
+            last_snapshot: None,
+            open: HashMap::new(),
+            episodes: Vec::new(),
+        }
+    }
+
+    fn close(&mut self, key: (String, String, String), truncated: bool) {
+        if let Some(open) = self.open.remove(&key) {
+            let (router, peer, label) = key;
+            self.episodes.push(Episode {
+                router,
+                peer,
+                label,
+                start: open.start,
+                end: open.end,
+                nb_snapshots: open.nb_snapshots,
+                peak: open.peak,
+                mean: open.sum as f64 / open.nb_snapshots as f64,
+                truncated,
+            });
+        }
+    }
+
+    /// Closes the episodes matching `predicate`.
+    fn close_where<F>(&mut self, predicate: F, truncated: bool)
+    where
+        F: Fn(&OpenEpisode) -> bool,
+    {
+        let keys = self
+            .open
+            .iter()
+            .filter(|(_, open)| predicate(open))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<(String, String, String)>>();
+        for key in keys {
+            self.close(key, truncated);
+        }
+    }
+
+    pub fn push(&mut self, data: &OvhData) {
+        let now = data.timestamp;
+        if let Some(last) = self.last_snapshot {
+            if now <= last {
+                return;
+            }
+            if now - last > self.max_gap {
+                // Hole in the data: whether the links stayed loaded is unknown
+                self.close_where(|_| true, true);
+            }
+        }
+        self.last_snapshot = Some(now);
+
+        for router in data.data.values() {
+            for (peer, links) in router.peers.iter() {
+                let mut seen: HashMap<&str, usize> = HashMap::new();
+                for link in links.iter() {
+                    // Same labelling of the duplicate labels as the load store
+                    let occurrence = seen.entry(&link.label).or_insert(0);
+                    *occurrence += 1;
+                    if link.load < self.threshold {
+                        continue;
+                    }
+                    let label = match *occurrence {
+                        1 => link.label.to_string(),
+                        n => format!("{}/{}", link.label, n),
+                    };
+                    let open = self
+                        .open
+                        .entry((router.name.to_string(), peer.to_string(), label))
+                        .or_insert(OpenEpisode {
+                            start: now,
+                            end: now,
+                            nb_snapshots: 0,
+                            peak: 0,
+                            sum: 0,
+                        });
+                    open.end = now;
+                    open.nb_snapshots += 1;
+                    open.peak = open.peak.max(link.load);
+                    open.sum += link.load as u64;
+                }
+            }
+        }
+
+        let max_gap = self.max_gap;
+        self.close_where(|open| now - open.end > max_gap, false);
+    }
+
+    /// All the episodes, sorted by start. The episodes still ongoing are truncated.
+    pub fn finish(mut self) -> Vec<Episode> {
+        self.close_where(|_| true, true);
+        self.episodes.sort_by(|a, b| {
+            (a.start, &a.router, &a.peer, &a.label).cmp(&(b.start, &b.router, &b.peer, &b.label))
+        });
+        self.episodes
+    }
+}
+
+/// Writes the episodes as `router,peer,label,start,end,nb_snapshots,peak,mean,truncated`,
+/// with headers and unix timestamps.
+pub fn write_episodes_csv<W: Write>(episodes: &[Episode], wrt: W) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record([
+        "router",
+        "peer",
+        "label",
+        "start",
+        "end",
+        "nb_snapshots",
+        "peak",
+        "mean",
+        "truncated",
+    ])?;
+    for episode in episodes {
+        wrt.write_record([
+            episode.router.to_string(),
+            episode.peer.to_string(),
+            episode.label.to_string(),
+            episode.start.and_utc().timestamp().to_string(),
+            episode.end.and_utc().timestamp().to_string(),
+            episode.nb_snapshots.to_string(),
+            episode.peak.to_string(),
+            format!("{:.2}", episode.mean),
+            episode.truncated.to_string(),
+        ])?;
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Link, Router};
+    use chrono::DateTime;
+
+    fn data(t: i64, load: u32) -> OvhData {
+        let router = Router {
+            name: "a".to_string(),
+            peers: HashMap::from([(
+                "b".to_string(),
+                vec![Link {
+                    label: "#1".to_string(),
+                    load,
+                }],
+            )]),
+        };
+        OvhData {
+            timestamp: DateTime::from_timestamp(t, 0).unwrap().naive_utc(),
+            data: HashMap::from([("a".to_string(), router)]),
+        }
+    }
+
+    #[test]
+    fn test_episodes() {
+        let mut detector = HighLoadDetector::new(90, Duration::minutes(15));
+        let loads = [
+            (0, 95),
+            (300, 80),
+            // A short dip does not split the episode
+            (600, 99),
+            (900, 50),
+            (1200, 50),
+            (1500, 50),
+            (1800, 50),
+            // New episode, interrupted by a hole in the data
+            (2100, 91),
+            (2400, 93),
+            (6000, 50),
+            // Ongoing at the end of the data
+            (6300, 92),
+        ];
+        for (t, load) in loads {
+            detector.push(&data(t, load));
+        }
+        let episodes = detector.finish();
+        assert_eq!(
+            episodes
+                .iter()
+                .map(|e| (
+                    e.start.and_utc().timestamp(),
+                    e.end.and_utc().timestamp(),
+                    e.nb_snapshots,
+                    e.peak,
+                    e.truncated
+                ))
+                .collect::<Vec<(i64, i64, usize, u32, bool)>>(),
+            vec![
+                (0, 600, 2, 99, false),
+                (2100, 2400, 2, 93, true),
+                (6300, 6300, 1, 92, true),
+            ]
+        );
+        assert_eq!(episodes[0].mean, 97.0);
+    }
+}
@@ -0,0 +1,245 @@
+//! Histograms with half-open bins `[a,b[`, used to aggregate the loads, the ECMP diffs
+//! and the sizes of the ECMP groups.
+//!
+//! Bins are written as a list of edges (`0,1,2,5,100`), as `linear:<min>:<max>:<nb bins>`
+//! for bins of equal width, or as `log:<min>:<max>:<nb bins>` for bins of equal ratio.
+//! Values outside of `[first edge, last edge[` are not counted.
+
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::str::FromStr;
+
+/// Consecutive half-open bins, given by their strictly increasing edges.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bins {
+    edges: Vec<f64>,
+}
+
+impl Bins {
+    /// Bins between the given edges, which must be at least two and strictly increasing.
+    pub fn explicit(edges: Vec<f64>) -> Result<Bins, String> {
+        if edges.len() < 2 {
+            return Err("At least two bin edges are needed".to_string());
+        }
+        if edges.iter().any(|e| !e.is_finite()) {
+            return Err("Bin edges must be finite".to_string());
+        }
+        if edges.windows(2).any(|w| w[0] >= w[1]) {
+            return Err("Bin edges must be strictly increasing".to_string());
+        }
+        Ok(Bins { edges })
+    }
+
+    /// `nb_bins` bins of equal width between `min` and `max`.
+    pub fn linear(min: f64, max: f64, nb_bins: usize) -> Result<Bins, String> {
+        if nb_bins == 0 {
+            return Err("At least one bin is needed".to_string());
+        }
+        let width = (max - min) / nb_bins as f64;
+        Bins::explicit(
+            (0..=nb_bins)
+                .map(|i| {
+                    if i == nb_bins {
+                        max
+                    } else {
+                        min + width * i as f64
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// `nb_bins` bins whose edges grow geometrically from `min` to `max`, with `min > 0`.
+    pub fn log(min: f64, max: f64, nb_bins: usize) -> Result<Bins, String> {
+        if min <= 0.0 {
+            return Err("Logarithmic bins must start above 0".to_string());
+        }
+        let linear = Bins::linear(min.ln(), max.ln(), nb_bins)?;
+        Bins::explicit(
+            linear
+                .edges
+                .iter()
+                .enumerate()
+                .map(|(i, e)| match i {
+                    0 => min,
+                    i if i == nb_bins => max,
+                    _ => e.exp(),
+                })
+                .collect(),
+        )
+    }
+
+    pub fn edges(&self) -> &[f64] {
+        &self.edges
+    }
+
+    pub fn len(&self) -> usize {
+        self.edges.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Index of the bin containing `value`, if any.
+    pub fn index_of(&self, value: f64) -> Option<usize> {
+        if value < self.edges[0] || value >= self.edges[self.len()] {
+            return None;
+        }
+        // Number of edges lower or equal to the value, the first one included
+        Some(self.edges.partition_point(|&e| e <= value) - 1)
+    }
+
+    /// Number of values in each bin.
+    pub fn count<I>(&self, values: I) -> Vec<usize>
+    where
+        I: IntoIterator,
+        I::Item: Into<f64>,
+    {
+        let mut counts = vec![0; self.len()];
+        for value in values {
+            if let Some(idx) = self.index_of(value.into()) {
+                counts[idx] += 1;
+            }
+        }
+        counts
+    }
+
+    /// The bins, written `[a,b[`.
+    pub fn labels(&self) -> Vec<String> {
+        let edge = |e: f64| format!("{}", (e * 100.0).round() / 100.0);
+        self.edges
+            .windows(2)
+            .map(|w| format!("[{},{}[", edge(w[0]), edge(w[1])))
+            .collect()
+    }
+}
+
+impl fmt::Display for Bins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let edges = self
+            .edges
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "{}", edges.join(","))
+    }
+}
+
+impl FromStr for Bins {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Bins, String> {
+        let number = |v: &str| {
+            v.trim()
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid number in bins {}: {}", s, v))
+        };
+        let generated = |parts: &[&str]| -> Result<(f64, f64, usize), String> {
+            match parts {
+                [min, max, nb_bins] => Ok((
+                    number(min)?,
+                    number(max)?,
+                    nb_bins
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid number of bins: {}", nb_bins))?,
+                )),
+                _ => Err(format!("Expected <kind>:<min>:<max>:<nb bins>, got {}", s)),
+            }
+        };
+
+        let parts = s.split(':').collect::<Vec<&str>>();
+        match parts[0] {
+            "linear" => {
+                let (min, max, nb_bins) = generated(&parts[1..])?;
+                Bins::linear(min, max, nb_bins)
+            }
+            "log" => {
+                let (min, max, nb_bins) = generated(&parts[1..])?;
+                Bins::log(min, max, nb_bins)
+            }
+            _ if parts.len() == 1 => Bins::explicit(
+                s.split(',')
+                    .map(number)
+                    .collect::<Result<Vec<f64>, String>>()?,
+            ),
+            kind => Err(format!("Unknown kind of bins: {}", kind)),
+        }
+    }
+}
+
+/// Bins of each aggregated metric.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinsConfig {
+    pub ecmp_diffs: Bins,
+    pub loads: Bins,
+    pub ecmp_sizes: Bins,
+}
+
+impl Default for BinsConfig {
+    fn default() -> Self {
+        BinsConfig {
+            ecmp_diffs: Bins::explicit(vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 100.0])
+                .unwrap(),
+            loads: Bins::linear(0.0, 110.0, 11).unwrap(),
+            ecmp_sizes: Bins::log(1.0, 128.0, 7).unwrap(),
+        }
+    }
+}
+
+/// The bins as written in a configuration file, every metric being optional.
+#[derive(Debug, Deserialize)]
+struct BinsFile {
+    ecmp_diffs: Option<String>,
+    loads: Option<String>,
+    ecmp_sizes: Option<String>,
+}
+
+impl BinsConfig {
+    /// Reads a YAML file such as `{ecmp_diffs: "0,1,2,5,100", loads: "linear:0:100:4"}`.
+    /// The metrics that are not given keep their default bins.
+    pub fn from_yaml_file(filepath: &str) -> Result<BinsConfig, Box<dyn Error>> {
+        let fd = File::open(filepath)?;
+        let file: BinsFile = serde_yaml::from_reader(fd)?;
+        let mut config = BinsConfig::default();
+        for (bins, spec) in [
+            (&mut config.ecmp_diffs, file.ecmp_diffs),
+            (&mut config.loads, file.loads),
+            (&mut config.ecmp_sizes, file.ecmp_sizes),
+        ] {
+            if let Some(spec) = spec {
+                *bins = spec.parse::<Bins>()?;
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_half_open_bins() {
+        let bins = "0,1,2,5,100".parse::<Bins>().unwrap();
+        assert_eq!(bins.labels(), vec!["[0,1[", "[1,2[", "[2,5[", "[5,100["]);
+        assert_eq!(bins.index_of(-1.0), None);
+        assert_eq!(bins.index_of(0.0), Some(0));
+        assert_eq!(bins.index_of(3.0), Some(2));
+        assert_eq!(bins.index_of(99.5), Some(3));
+        assert_eq!(bins.index_of(100.0), None);
+        assert_eq!(bins.count([0i8, 0, 1, 3, 4, 50, 100]), vec![2, 1, 2, 1]);
+
+        let linear = "linear:0:100:4".parse::<Bins>().unwrap();
+        assert_eq!(linear.edges(), &[0.0, 25.0, 50.0, 75.0, 100.0]);
+        let log = "log:1:1000:3".parse::<Bins>().unwrap();
+        assert_eq!(log.labels(), vec!["[1,10[", "[10,100[", "[100,1000["]);
+
+        assert!("3,2".parse::<Bins>().is_err());
+        assert!("log:0:10:2".parse::<Bins>().is_err());
+        assert!("linear:0:10".parse::<Bins>().is_err());
+    }
+}
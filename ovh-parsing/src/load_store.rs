@@ -0,0 +1,525 @@
+//! Columnar store of the link loads over time.
+//!
+//! The store keeps, for each link entry of the snapshots, the column of the snapshots
+//! where the entry appears and the column of its loads. Both columns are delta-encoded
+//! as variable-length integers, which makes a multi-year history fit in memory, and
+//! lets every analysis query a link history without parsing the YAML files again.
+
+use crate::{FileMetadata, OvhData, ParseError, SnapshotCache};
+use bincode::Options;
+use chrono::{DateTime, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::ops::{Bound, RangeBounds};
+
+/// Direction of the traffic on a link, seen from `LinkKey::router`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    /// From `router` to `peer`: the link as listed by `router`.
+    Up,
+    /// From `peer` to `router`: the link as listed by `peer`.
+    Down,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+        }
+    }
+}
+
+/// Identifies the load series of one link entry.
+/// If a router lists several links with the same label towards the same peer,
+/// the second one gets the label `<label>/2`, the third one `<label>/3`, etc.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct LinkKey {
+    pub router: String,
+    pub peer: String,
+    pub label: String,
+    pub direction: Direction,
+}
+
+impl LinkKey {
+    /// The same entry, seen from the other end.
+    pub fn reversed(&self) -> LinkKey {
+        LinkKey {
+            router: self.peer.to_string(),
+            peer: self.router.to_string(),
+            label: self.label.to_string(),
+            direction: match self.direction {
+                Direction::Up => Direction::Down,
+                Direction::Down => Direction::Up,
+            },
+        }
+    }
+}
+
+fn write_varint(column: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        column.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    column.push(value as u8);
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_varint(column: &[u8], pos: &mut usize) -> io::Result<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = *column
+            .get(*pos)
+            .ok_or_else(|| invalid_data("truncated variable-length integer"))?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte < 0x80 {
+            return Ok(value);
+        }
+        shift += 7;
+        if shift > 63 {
+            return Err(invalid_data("variable-length integer longer than 64 bits"));
+        }
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Delta-encoded columns of one link entry.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct EncodedSeries {
+    len: usize,
+    /// Deltas between the indexes (in `LoadStore::timestamps`) of consecutive values.
+    snapshots: Vec<u8>,
+    /// Zigzag-encoded deltas between consecutive loads.
+    loads: Vec<u8>,
+    last_snapshot: u32,
+    last_load: u32,
+}
+
+impl EncodedSeries {
+    fn push(&mut self, snapshot: u32, load: u32) {
+        let (previous_snapshot, previous_load) = match self.len {
+            0 => (0, 0),
+            _ => (self.last_snapshot, self.last_load),
+        };
+        write_varint(&mut self.snapshots, (snapshot - previous_snapshot) as u64);
+        write_varint(&mut self.loads, zigzag(load as i64 - previous_load as i64));
+        self.last_snapshot = snapshot;
+        self.last_load = load;
+        self.len += 1;
+    }
+
+    /// Decodes the values whose snapshot index is in `first..last`.
+    fn decode(&self, first: u32, last: u32) -> io::Result<Vec<(u32, u32)>> {
+        let mut output = Vec::new();
+        let (mut pos_snapshots, mut pos_loads) = (0, 0);
+        let (mut snapshot, mut load) = (0u32, 0i64);
+        for _ in 0..self.len {
+            let delta = read_varint(&self.snapshots, &mut pos_snapshots)?;
+            snapshot = u32::try_from(delta)
+                .ok()
+                .and_then(|delta| snapshot.checked_add(delta))
+                .ok_or_else(|| invalid_data("snapshot index out of range"))?;
+            load = load
+                .checked_add(unzigzag(read_varint(&self.loads, &mut pos_loads)?))
+                .ok_or_else(|| invalid_data("load out of range"))?;
+            if snapshot >= last {
+                break;
+            }
+            if snapshot >= first {
+                let load = u32::try_from(load).map_err(|_| invalid_data("load out of range"))?;
+                output.push((snapshot, load));
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// Loads of a link over time, in increasing timestamp order.
+pub type LoadSeries = Vec<(NaiveDateTime, u32)>;
+
+/// Columnar store of the load of every link entry over time.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LoadStore {
+    /// Timestamps of all the snapshots pushed in the store, sorted.
+    timestamps: Vec<i64>,
+    /// Series indexed by the listing router, so always with `Direction::Up`.
+    series: BTreeMap<LinkKey, EncodedSeries>,
+}
+
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new().with_varint_encoding()
+}
+
+impl LoadStore {
+    pub fn new() -> LoadStore {
+        LoadStore::default()
+    }
+
+    /// Builds the store from snapshot files, parsing them (or loading them from `cache`) in order.
+    /// Files that cannot be parsed are returned alongside the store.
+    pub fn from_files(
+        files: &[FileMetadata],
+        cache: Option<&SnapshotCache>,
+    ) -> (LoadStore, Vec<ParseError>) {
+        let mut sorted = files.iter().collect::<Vec<&FileMetadata>>();
+        sorted.sort_by_key(|f| f.timestamp);
+        let mut store = LoadStore::new();
+        let mut errors = Vec::new();
+        for file in sorted {
+            let parsed = match cache {
+                Some(cache) => cache.parse(file),
+                None => file.parse(),
+            };
+            match parsed {
+                Ok(data) => {
+                    store.push(&data);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+        (store, errors)
+    }
+
+    /// Appends a snapshot. Snapshots must be pushed in increasing timestamp order;
+    /// a snapshot older than the last one is ignored and `false` is returned.
+    pub fn push(&mut self, data: &OvhData) -> bool {
+        let timestamp = data.timestamp.and_utc().timestamp();
+        if let Some(&last) = self.timestamps.last() {
+            if timestamp <= last {
+                return false;
+            }
+        }
+        let snapshot = self.timestamps.len() as u32;
+        self.timestamps.push(timestamp);
+
+        for router in data.data.values() {
+            for (peer_name, links) in router.peers.iter() {
+                let mut seen: HashMap<&str, usize> = HashMap::new();
+                for link in links {
+                    let occurrence = seen.entry(&link.label).or_insert(0);
+                    *occurrence += 1;
+                    let label = match *occurrence {
+                        1 => link.label.to_string(),
+                        n => format!("{}/{}", link.label, n),
+                    };
+                    let key = LinkKey {
+                        router: router.name.to_string(),
+                        peer: peer_name.to_string(),
+                        label,
+                        direction: Direction::Up,
+                    };
+                    self.series
+                        .entry(key)
+                        .or_default()
+                        .push(snapshot, link.load);
+                }
+            }
+        }
+        true
+    }
+
+    /// Number of snapshots in the store.
+    pub fn nb_snapshots(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    /// Timestamps of the snapshots in the store.
+    pub fn timestamps(&self) -> Vec<NaiveDateTime> {
+        self.timestamps.iter().map(|&t| to_datetime(t)).collect()
+    }
+
+    /// All the keys, in both directions.
+    pub fn keys(&self) -> impl Iterator<Item = LinkKey> + '_ {
+        self.series
+            .keys()
+            .flat_map(|key| [key.clone(), key.reversed()])
+    }
+
+    /// Converts a time range into a range of snapshot indexes.
+    fn snapshot_range<R: RangeBounds<NaiveDateTime>>(&self, range: &R) -> (u32, u32) {
+        let first = match range.start_bound() {
+            Bound::Included(t) => {
+                let t = t.and_utc().timestamp();
+                self.timestamps.partition_point(|&x| x < t)
+            }
+            Bound::Excluded(t) => {
+                let t = t.and_utc().timestamp();
+                self.timestamps.partition_point(|&x| x <= t)
+            }
+            Bound::Unbounded => 0,
+        };
+        let last = match range.end_bound() {
+            Bound::Included(t) => {
+                let t = t.and_utc().timestamp();
+                self.timestamps.partition_point(|&x| x <= t)
+            }
+            Bound::Excluded(t) => {
+                let t = t.and_utc().timestamp();
+                self.timestamps.partition_point(|&x| x < t)
+            }
+            Bound::Unbounded => self.timestamps.len(),
+        };
+        (first as u32, last as u32)
+    }
+
+    fn decode<R: RangeBounds<NaiveDateTime>>(
+        &self,
+        series: &EncodedSeries,
+        range: &R,
+    ) -> LoadSeries {
+        let (first, last) = self.snapshot_range(range);
+        series
+            .decode(first, last)
+            .expect("the series are checked when the store is loaded")
+            .into_iter()
+            .map(|(snapshot, load)| (to_datetime(self.timestamps[snapshot as usize]), load))
+            .collect()
+    }
+
+    /// Loads of one link entry within `range`.
+    pub fn link<R: RangeBounds<NaiveDateTime>>(
+        &self,
+        key: &LinkKey,
+        range: R,
+    ) -> Option<LoadSeries> {
+        let stored = match key.direction {
+            Direction::Up => self.series.get(key),
+            Direction::Down => self.series.get(&key.reversed()),
+        }?;
+        Some(self.decode(stored, &range))
+    }
+
+    /// Loads of all the links between `router` and `peer` within `range`, in both directions.
+    pub fn between<R: RangeBounds<NaiveDateTime>>(
+        &self,
+        router: &str,
+        peer: &str,
+        range: R,
+    ) -> Vec<(LinkKey, LoadSeries)> {
+        let mut output = Vec::new();
+        for (from, to, reversed) in [(router, peer, false), (peer, router, true)] {
+            for (key, series) in self.entries_of(from).filter(|(key, _)| key.peer == to) {
+                let key = if reversed {
+                    key.reversed()
+                } else {
+                    key.clone()
+                };
+                output.push((key, self.decode(series, &range)));
+            }
+        }
+        output
+    }
+
+    /// Loads of all the links of `router` within `range`, in both directions.
+    pub fn router<R: RangeBounds<NaiveDateTime>>(
+        &self,
+        router: &str,
+        range: R,
+    ) -> Vec<(LinkKey, LoadSeries)> {
+        let mut output = self
+            .entries_of(router)
+            .map(|(key, series)| (key.clone(), self.decode(series, &range)))
+            .collect::<Vec<(LinkKey, LoadSeries)>>();
+        output.extend(
+            self.series
+                .iter()
+                .filter(|(key, _)| key.peer == router)
+                .map(|(key, series)| (key.reversed(), self.decode(series, &range))),
+        );
+        output
+    }
+
+    /// Stored entries listed by `router`.
+    fn entries_of<'a>(
+        &'a self,
+        router: &'a str,
+    ) -> impl Iterator<Item = (&'a LinkKey, &'a EncodedSeries)> + 'a {
+        let start = LinkKey {
+            router: router.to_string(),
+            peer: String::new(),
+            label: String::new(),
+            direction: Direction::Up,
+        };
+        self.series
+            .range(start..)
+            .take_while(move |(key, _)| key.router == router)
+    }
+
+    /// Saves the store in a compact binary file.
+    pub fn save(&self, filepath: &str) -> Result<(), Box<dyn Error>> {
+        let wrt = BufWriter::new(File::create(filepath)?);
+        bincode_options().serialize_into(wrt, self)?;
+        Ok(())
+    }
+
+    /// Loads a store saved with `save`.
+    /// A corrupted or truncated file gives an error of kind `io::ErrorKind::InvalidData`.
+    pub fn load(filepath: &str) -> Result<LoadStore, Box<dyn Error>> {
+        let fd = File::open(filepath)?;
+        // A corrupted length prefix must not make bincode allocate more than the file holds
+        let limit = fd.metadata()?.len();
+        let store: LoadStore = bincode_options()
+            .with_limit(limit)
+            .deserialize_from(BufReader::new(fd))?;
+        // The columns are only decoded by the queries, so they are all checked here once
+        for series in store.series.values() {
+            let values = series.decode(0, u32::MAX)?;
+            let in_range = values
+                .last()
+                .is_none_or(|&(snapshot, _)| (snapshot as usize) < store.timestamps.len());
+            if values.len() != series.len || !in_range {
+                return Err(invalid_data("series inconsistent with the snapshots").into());
+            }
+        }
+        Ok(store)
+    }
+}
+
+fn to_datetime(timestamp: i64) -> NaiveDateTime {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .naive_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Link, Router};
+
+    fn snapshot(timestamp: i64, loads: &[(&str, &str, &str, u32)]) -> OvhData {
+        let mut data: HashMap<String, Router> = HashMap::new();
+        for &(router, peer, label, load) in loads {
+            data.entry(router.to_string())
+                .or_insert_with(|| Router {
+                    name: router.to_string(),
+                    peers: HashMap::new(),
+                })
+                .peers
+                .entry(peer.to_string())
+                .or_default()
+                .push(Link {
+                    label: label.to_string(),
+                    load,
+                });
+        }
+        OvhData {
+            timestamp: to_datetime(timestamp),
+            data,
+        }
+    }
+
+    #[test]
+    fn test_varint_zigzag() {
+        let mut column = Vec::new();
+        for value in [0u64, 1, 127, 128, 300, u32::MAX as u64] {
+            write_varint(&mut column, value);
+        }
+        let mut pos = 0;
+        for value in [0u64, 1, 127, 128, 300, u32::MAX as u64] {
+            assert_eq!(read_varint(&column, &mut pos).unwrap(), value);
+        }
+        for value in [0i64, -1, 1, -100, 100] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+    }
+
+    #[test]
+    fn test_invalid_varint() {
+        let kind = |column: &[u8]| read_varint(column, &mut 0).unwrap_err().kind();
+        assert_eq!(kind(&[0x80]), io::ErrorKind::InvalidData);
+        assert_eq!(kind(&[0xff; 11]), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_load_truncated() {
+        let mut store = LoadStore::new();
+        store.push(&snapshot(100, &[("a", "b", "#1", 10)]));
+        store.push(&snapshot(200, &[("a", "b", "#1", 300)]));
+        for series in store.series.values_mut() {
+            series.loads.pop();
+        }
+        let path = std::env::temp_dir().join(format!("ovh-load-store-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        store.save(path).unwrap();
+        let error = LoadStore::load(path).unwrap_err();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(
+            error.downcast_ref::<io::Error>().map(|e| e.kind()),
+            Some(io::ErrorKind::InvalidData)
+        );
+    }
+
+    #[test]
+    fn test_load_garbage() {
+        let path =
+            std::env::temp_dir().join(format!("ovh-load-store-garbage-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        // Decodes as a huge length for the timestamps
+        std::fs::write(path, [0xff; 11]).unwrap();
+        let result = LoadStore::load(path);
+        std::fs::remove_file(path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut store = LoadStore::new();
+        store.push(&snapshot(
+            100,
+            &[("a", "b", "#1", 10), ("b", "a", "#1", 20)],
+        ));
+        store.push(&snapshot(200, &[("a", "b", "#1", 15), ("a", "c", "#1", 1)]));
+        store.push(&snapshot(300, &[("a", "b", "#1", 5), ("b", "a", "#1", 25)]));
+        assert!(!store.push(&snapshot(250, &[])));
+        assert_eq!(store.nb_snapshots(), 3);
+
+        let key = LinkKey {
+            router: "a".to_string(),
+            peer: "b".to_string(),
+            label: "#1".to_string(),
+            direction: Direction::Up,
+        };
+        let loads = store
+            .link(&key, ..)
+            .unwrap()
+            .into_iter()
+            .map(|(_, l)| l)
+            .collect::<Vec<u32>>();
+        assert_eq!(loads, vec![10, 15, 5]);
+
+        let range = to_datetime(150)..=to_datetime(300);
+        let down = LinkKey {
+            direction: Direction::Down,
+            ..key.clone()
+        };
+        assert_eq!(
+            store.link(&down, range.clone()).unwrap(),
+            vec![(to_datetime(300), 25)]
+        );
+        assert_eq!(store.link(&key.reversed(), range.clone()).unwrap().len(), 2);
+
+        let between = store.between("b", "a", ..);
+        assert_eq!(between.len(), 2);
+        assert!(between
+            .iter()
+            .any(|(k, s)| k.direction == Direction::Down && s.len() == 3));
+
+        let router = store.router("a", range);
+        assert_eq!(router.len(), 3);
+    }
+}
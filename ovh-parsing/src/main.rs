@@ -5,12 +5,18 @@ use chrono::Duration;
 use csv::{Writer, WriterBuilder};
 // use indicatif::ProgressBar;
 use ovh_parsing::{
-    aggregate_by_time, write_csv_ecmp_aggregated, ExperimentResults, FileMetadata, OvhNodeFilter,
+    aggregate_by_time,
+    buckets::BucketUnit,
+    get_files::get_all_ovh_files_with,
+    histogram::{Bins, BinsConfig},
+    summary::write_summaries_csv,
+    timeline::{write_gaps_csv, Timeline},
+    write_csv_histogram_aggregated, Dataset, ExperimentResults, FileMetadata, MapConfig, OvhMap,
+    OvhNodeFilter, SnapshotCache, TimestampExtractor, TimestampPattern,
 };
 use std::error::Error;
 use std::fs::File;
 use std::path::Path;
-use std::{fs, path::PathBuf};
 use structopt::StructOpt;
 
 use crate::multithreading::multithread_parsing;
@@ -19,6 +25,9 @@ mod multithreading;
 
 const UNIT_STEP: &[&str] = &["all", "hour", "day"];
 
+/// Values of a histogram in the results of a snapshot.
+type HistogramValues = fn(&ExperimentResults, OvhNodeFilter) -> &[i8];
+
 #[derive(StructOpt)]
 struct Cli {
     /// Directory containing the input yamls
@@ -44,6 +53,44 @@ struct Cli {
     /// If set, store non-aggregated results about ECMP diffs and link loads in YAML files
     #[structopt(long = "enable-full-load")]
     enable_full_load: bool,
+    /// Directory of the binary snapshot cache. Parsed snapshots are stored there
+    /// and reused by later runs as long as the source files do not change
+    #[structopt(long = "cache-dir")]
+    cache_dir: Option<String>,
+    /// Only analyse this map (europe, world, north-america, asia-pacific). The input directory
+    /// is then searched recursively, and each file is assigned to a map from its directory
+    #[structopt(long = "map")]
+    map: Option<OvhMap>,
+    /// YAML file mapping directory names to maps, e.g., `{data: europe, data_usa: north-america}`.
+    /// Defaults to the `data`, `data_world`, `data_usa` and `data_apac` layout
+    #[structopt(long = "map-config")]
+    map_config: Option<String>,
+    /// How the timestamp is written in the file names: `unix`, `iso8601` or a chrono format
+    /// such as `%Y-%m-%d_%H-%M`. May be repeated; defaults to `unix` and `iso8601`
+    #[structopt(long = "timestamp-pattern")]
+    timestamp_patterns: Vec<TimestampPattern>,
+    /// Give up on a file whose parsing takes longer than this number of seconds, 0 to wait for
+    /// every file. A file given up keeps its parsing thread busy until its parsing ends
+    #[structopt(long = "parse-timeout", default_value = "10")]
+    parse_timeout: u64,
+    /// Time buckets of the ECMP aggregation: `hour`, `day`, `week` (ISO), `month`, `quarter`
+    /// or a fixed duration such as `6h`, `30min` or `2d`
+    #[structopt(long = "aggregate-by", default_value = "month")]
+    aggregate_by: BucketUnit,
+    /// Bins of the ECMP diff histograms: edges such as `0,1,2,5,100`, `linear:<min>:<max>:<nb bins>`
+    /// or `log:<min>:<max>:<nb bins>`. Each bin `[a,b[` includes a but not b
+    #[structopt(long = "ecmp-bins")]
+    ecmp_bins: Option<Bins>,
+    /// Bins of the link load histograms, written as for `--ecmp-bins`
+    #[structopt(long = "load-bins")]
+    load_bins: Option<Bins>,
+    /// Bins of the ECMP group size histograms, written as for `--ecmp-bins`
+    #[structopt(long = "ecmp-size-bins")]
+    ecmp_size_bins: Option<Bins>,
+    /// YAML file giving the bins of some histograms, e.g., `{ecmp_diffs: "0,1,2,100",
+    /// loads: "linear:0:100:4"}`. The bins given on the command line take precedence
+    #[structopt(long = "bins-config")]
+    bins_config: Option<String>,
 }
 
 /// Returns a Vec of indexes of the files one should take given the `step`
@@ -77,7 +124,7 @@ fn cut_start_stop_timestamp(
     // TODO: could benefit from binary search because the array slice is sorted
     let start_idx = match files
         .iter()
-        .position(|file| file.timestamp.timestamp() == start_timestamp)
+        .position(|file| file.timestamp.and_utc().timestamp() == start_timestamp)
     {
         Some(idx) => idx,
         None => panic!("Could not find the desired timestamp: {}", start_timestamp),
@@ -87,7 +134,7 @@ fn cut_start_stop_timestamp(
     // If option was None => include until the end
     let final_timestamp = match stop_timestamp {
         Some(t) => t,
-        None => files[files.len() - 1].timestamp.timestamp(),
+        None => files[files.len() - 1].timestamp.and_utc().timestamp(),
     };
 
     if start_timestamp >= final_timestamp {
@@ -99,7 +146,7 @@ fn cut_start_stop_timestamp(
 
     let stop_idx = match files // Could skip the first few timestamps because higher than start
         .iter()
-        .position(|file| file.timestamp.timestamp() == final_timestamp)
+        .position(|file| file.timestamp.and_utc().timestamp() == final_timestamp)
     {
         Some(idx) => idx,
         None => panic!(
@@ -122,26 +169,43 @@ fn get_vec_values_from_idxs<'a, T>(vec: &'a [T], idxs: &[usize]) -> Vec<&'a T> {
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Cli::from_args();
 
-    // https://stackoverflow.com/questions/58062887/filtering-files-or-directories-discovered-with-fsread-dir
-    let paths: Vec<PathBuf> = fs::read_dir(&args.directory_path)
-        .unwrap_or_else(|_| {
+    let extractor = if args.timestamp_patterns.is_empty() {
+        TimestampExtractor::default()
+    } else {
+        TimestampExtractor::new(args.timestamp_patterns.clone())
+    };
+
+    // Plain, compressed and archived snapshots, sorted according to the timestamp
+    let (files, skipped) = match args.map {
+        Some(map) => {
+            let config = match &args.map_config {
+                Some(path) => MapConfig::from_yaml_file(path).unwrap_or_else(|e| {
+                    panic!("Impossible to read the map config {}: {}", path, e)
+                }),
+                None => MapConfig::default(),
+            };
+            let dataset = Dataset::discover_with(&args.directory_path, &config, &extractor)
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "Impossible to explore the directory {dir}: {e}",
+                        dir = &args.directory_path
+                    )
+                });
+            (dataset.files(map).to_vec(), dataset.skipped)
+        }
+        None => get_all_ovh_files_with(&args.directory_path, &extractor).unwrap_or_else(|e| {
             panic!(
-                "Impossible to open the directory: {dir}",
+                "Impossible to open the directory {dir}: {e}",
                 dir = &args.directory_path
             )
-        })
-        .into_iter()
-        .filter(|r| r.is_ok())
-        .map(|r| r.unwrap().path())
-        .collect();
-
-    let mut files = paths
-        .iter()
-        .filter_map(|pathbuf| FileMetadata::path_to_file_metadata(pathbuf))
-        .collect::<Vec<FileMetadata>>();
-
-    // Sort the files according to the timestamp
-    files.sort_by(|a, b| a.timestamp.partial_cmp(&b.timestamp).unwrap());
+        }),
+    };
+    for file in skipped.iter() {
+        println!("Skipping {}", file);
+    }
+    if files.is_empty() {
+        panic!("No snapshot found in {}", args.directory_path);
+    }
 
     let step_function = match args.unit_step.as_ref() {
         "hour" => |x: Duration| x.num_hours(),
@@ -167,9 +231,46 @@ fn main() -> Result<(), Box<dyn Error>> {
         two = idxs_selected.len()
     );
 
+    // Gaps in the collection over the analysed period, before sampling
+    let timeline = Timeline::from_files(sliced_time_window);
+    let gaps = timeline.gaps();
+    println!(
+        "{} gaps in the data, the longest of {} hours",
+        gaps.len(),
+        gaps.iter()
+            .map(|g| g.duration().num_hours())
+            .max()
+            .unwrap_or_default()
+    );
+    let map_name = args.map.map_or("all".to_string(), |map| map.to_string());
+    write_gaps_csv(
+        &[(map_name, timeline)],
+        File::create(Path::new(&args.output_dir).join("timeline-gaps.csv"))?,
+    )?;
+
     let files_selected = get_vec_values_from_idxs(sliced_time_window, &idxs_selected);
 
-    let all_results = multithread_parsing(&files_selected, args.nb_threads as usize);
+    let cache = args.cache_dir.as_ref().map(|dir| {
+        SnapshotCache::new(dir)
+            .unwrap_or_else(|e| panic!("Impossible to use the cache directory {}: {}", dir, e))
+    });
+    let (all_results, report) = multithread_parsing(
+        &files_selected,
+        args.nb_threads as usize,
+        cache,
+        match args.parse_timeout {
+            0 => None,
+            seconds => Some(std::time::Duration::from_secs(seconds)),
+        },
+    );
+    // Every selected file and what happened to it, failed ones included
+    println!("{}", report.summary());
+    report.write_csv(
+        Path::new(&args.output_dir)
+            .join("parsing-report.csv")
+            .to_str()
+            .unwrap(),
+    )?;
     let all_writers = [
         |x: &ExperimentResults, wrt: &mut Writer<File>| {
             x.write_csv_nb_nodes(wrt, OvhNodeFilter::All)
@@ -209,41 +310,71 @@ fn main() -> Result<(), Box<dyn Error>> {
             .for_each(|res| wrt_fn(res, &mut wrt).unwrap())
     }
 
-    // ECMP aggregation
-    let aggregated = aggregate_by_time(&all_results);
-    let ranges: &[i8; 9] = &[0, 1, 2, 3, 4, 5, 6, 7, 100];
-    let ranges_str: Vec<String> = ranges
-        .windows(2)
-        .map(|slice| format!("[{},{}[", slice[0], slice[1]))
-        .collect();
-
-    let all_ecmp_imbalance_files = [
-        |x: &str| format!("ecmp-agg-{}-all.csv", x),
-        |x: &str| format!("ecmp-agg-{}-ovh.csv", x),
-        |x: &str| format!("ecmp-agg-{}-external.csv", x),
+    // Histograms of the ECMP diffs, link loads and ECMP group sizes, per time bucket
+    let aggregated = aggregate_by_time(&all_results, args.aggregate_by);
+    let mut bins = match &args.bins_config {
+        Some(path) => BinsConfig::from_yaml_file(path)
+            .unwrap_or_else(|e| panic!("Impossible to read the bins config {}: {}", path, e)),
+        None => BinsConfig::default(),
+    };
+    if let Some(ecmp_bins) = &args.ecmp_bins {
+        bins.ecmp_diffs = ecmp_bins.clone();
+    }
+    if let Some(load_bins) = &args.load_bins {
+        bins.loads = load_bins.clone();
+    }
+    if let Some(ecmp_size_bins) = &args.ecmp_size_bins {
+        bins.ecmp_sizes = ecmp_size_bins.clone();
+    }
+
+    let all_histograms: [(&str, &Bins, HistogramValues); 3] = [
+        (
+            "ecmp-agg",
+            &bins.ecmp_diffs,
+            ExperimentResults::ecmp_diffs_of,
+        ),
+        ("load-agg", &bins.loads, ExperimentResults::loads_of),
+        (
+            "ecmp-size-agg",
+            &bins.ecmp_sizes,
+            ExperimentResults::nb_ecmp_links_of,
+        ),
     ];
 
     let all_routers_options = [
-        OvhNodeFilter::All,
-        OvhNodeFilter::Ovh,
-        OvhNodeFilter::External,
+        (OvhNodeFilter::All, "all"),
+        (OvhNodeFilter::Ovh, "ovh"),
+        (OvhNodeFilter::External, "external"),
     ];
 
-    for (filename, option) in all_ecmp_imbalance_files.iter().zip(all_routers_options) {
-        let mut wrt_values = WriterBuilder::new()
-            .has_headers(true)
-            .delimiter(b';')
-            .from_path(Path::new(&args.output_dir).join(filename("values")))?;
-        let mut wrt_total = WriterBuilder::new()
-            .has_headers(true)
-            .delimiter(b';')
-            .from_path(Path::new(&args.output_dir).join(filename("total")))?;
-        // Write the headers
-        wrt_values.serialize(("Time", &ranges_str))?;
-        wrt_total.serialize(("Time", "Total"))?;
-        write_csv_ecmp_aggregated(&aggregated, &mut wrt_values, &mut wrt_total, option, ranges)?;
+    for (prefix, bins, values) in all_histograms {
+        for (option, suffix) in all_routers_options {
+            let filename = |x: &str| format!("{}-{}-{}.csv", prefix, x, suffix);
+            let mut wrt_values = WriterBuilder::new()
+                .has_headers(true)
+                .delimiter(b';')
+                .from_path(Path::new(&args.output_dir).join(filename("values")))?;
+            let mut wrt_total = WriterBuilder::new()
+                .has_headers(true)
+                .delimiter(b';')
+                .from_path(Path::new(&args.output_dir).join(filename("total")))?;
+            // Write the headers
+            wrt_values.serialize(("Time", bins.labels()))?;
+            wrt_total.serialize(("Time", "Total"))?;
+            write_csv_histogram_aggregated(
+                &aggregated,
+                &mut wrt_values,
+                &mut wrt_total,
+                |exp| values(exp, option),
+                bins,
+            )?;
+        }
     }
 
+    // Distribution of the same values, per time bucket
+    let summaries_path = Path::new(&args.output_dir).join("summaries.csv");
+    write_summaries_csv(&aggregated, File::create(summaries_path)?)?;
+
     if args.enable_full_load {
         // ECMP full loads
         let all_writers_yaml = [
@@ -1,73 +1,191 @@
 // Author: Louis Navarre <louis.navarre@uclouvain.be> (UCLouvain -- INL)
 // Date: 11/05/2022
 
+use csv::WriterBuilder;
 use indicatif::ProgressBar;
-use ovh_parsing::{parse_yaml, ExperimentResults, FileMetadata, OvhNodeFilter};
-use std::sync::mpsc::channel;
+use ovh_parsing::stream::{SnapshotStream, TaskOutcome};
+use ovh_parsing::{ExperimentResults, FileMetadata, OvhData, OvhNodeFilter, SnapshotCache};
+use std::error::Error;
 use std::time::Duration;
-use threadpool::ThreadPool;
+
+/// What happened to one file during the parsing.
+#[derive(Debug)]
+pub enum FileOutcome {
+    Ok,
+    ParseError(String),
+    Timeout,
+    Panic(String),
+}
+
+impl FileOutcome {
+    fn name(&self) -> &'static str {
+        match self {
+            FileOutcome::Ok => "ok",
+            FileOutcome::ParseError(_) => "parse-error",
+            FileOutcome::Timeout => "timeout",
+            FileOutcome::Panic(_) => "panic",
+        }
+    }
+
+    fn detail(&self) -> &str {
+        match self {
+            FileOutcome::ParseError(msg) | FileOutcome::Panic(msg) => msg,
+            FileOutcome::Ok | FileOutcome::Timeout => "",
+        }
+    }
+}
+
+/// Outcome of each file given to `multithread_parsing`, in timestamp order.
+#[derive(Debug, Default)]
+pub struct ParsingReport {
+    pub outcomes: Vec<(FileMetadata, FileOutcome)>,
+}
+
+impl ParsingReport {
+    pub fn nb_ok(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| matches!(outcome, FileOutcome::Ok))
+            .count()
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &(FileMetadata, FileOutcome)> {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| !matches!(outcome, FileOutcome::Ok))
+    }
+
+    /// One line per failure kind, e.g., `Parsed 10/12 files: 1 parse-error, 1 timeout`.
+    pub fn summary(&self) -> String {
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for (_, outcome) in self.failed() {
+            match counts.iter_mut().find(|(name, _)| *name == outcome.name()) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((outcome.name(), 1)),
+            }
+        }
+        let mut summary = format!("Parsed {}/{} files", self.nb_ok(), self.outcomes.len());
+        if !counts.is_empty() {
+            let counts = counts
+                .iter()
+                .map(|(name, count)| format!("{} {}", count, name))
+                .collect::<Vec<String>>();
+            summary.push_str(&format!(": {}", counts.join(", ")));
+        }
+        summary
+    }
+
+    /// Writes the outcome of every file, so that the set of snapshots behind the results is known.
+    pub fn write_csv(&self, filepath: &str) -> Result<(), Box<dyn Error>> {
+        let mut wrt = WriterBuilder::new().has_headers(true).from_path(filepath)?;
+        wrt.write_record(["file", "timestamp", "outcome", "detail"])?;
+        for (file, outcome) in self.outcomes.iter() {
+            wrt.write_record([
+                file.display_path(),
+                file.timestamp.and_utc().timestamp().to_string(),
+                outcome.name().to_string(),
+                outcome.detail().to_string(),
+            ])?;
+        }
+        wrt.flush()?;
+        Ok(())
+    }
+}
+
+fn experiment_results(val: &OvhData) -> ExperimentResults {
+    let nb_nodes = val.get_nb_nodes(OvhNodeFilter::All);
+    let nb_nodes_ovh = val.get_nb_nodes(OvhNodeFilter::Ovh);
+    let nb_nodes_external = val.get_nb_nodes(OvhNodeFilter::External);
+    let nb_links = val.get_nb_links(OvhNodeFilter::All);
+    let nb_links_external = val.get_nb_links(OvhNodeFilter::External);
+    let ecmp_diffs = val.get_ecmp_imbalance(OvhNodeFilter::All);
+    let ecmp_diffs_ovh = val.get_ecmp_imbalance(OvhNodeFilter::Ovh);
+    let ecmp_diffs_external = val.get_ecmp_imbalance(OvhNodeFilter::External);
+    let loads = val.get_link_loads(OvhNodeFilter::All);
+    let loads_ovh = val.get_link_loads(OvhNodeFilter::Ovh);
+    let loads_external = val.get_link_loads(OvhNodeFilter::External);
+    let nb_ecmp_links = val.get_nb_ecmp_links(OvhNodeFilter::All);
+    let nb_ecmp_links_ovh = val.get_nb_ecmp_links(OvhNodeFilter::Ovh);
+    let nb_ecmp_links_external = val.get_nb_ecmp_links(OvhNodeFilter::External);
+    // Easier, because we should divide by two for internal links, but by 1
+    // for peering links.
+    let nb_links_ovh = nb_links - nb_links_external;
+    ExperimentResults {
+        timestamp: val.timestamp,
+        nb_nodes,
+        nb_nodes_ovh,
+        nb_nodes_external,
+        nb_links,
+        nb_links_ovh,
+        nb_links_external,
+        ecmp_diffs,
+        ecmp_diffs_ovh,
+        ecmp_diffs_external,
+        loads,
+        loads_ovh,
+        loads_external,
+        nb_ecmp_links,
+        nb_ecmp_links_ovh,
+        nb_ecmp_links_external,
+        // ..Default::default()  // Just in case we add other fields, the code compiles
+    }
+}
 
 /// https://rust-lang-nursery.github.io/rust-cookbook/concurrency/threads.html
-pub fn multithread_parsing(files: &[&FileMetadata], nb_threads: usize) -> Vec<ExperimentResults> {
-    let pool = ThreadPool::new(nb_threads);
-    let (tx, rx) = channel();
+/// If a `cache` is given, snapshots are loaded from it when possible, and stored in it otherwise.
+/// A file taking longer than `timeout` to be parsed is given up: it is reported as timed out,
+/// but keeps its thread of the pool busy until its parsing ends.
+/// Every file ends up in the report, whether it could be parsed or not.
+pub fn multithread_parsing(
+    files: &[&FileMetadata],
+    nb_threads: usize,
+    cache: Option<SnapshotCache>,
+    timeout: Option<Duration>,
+) -> (Vec<ExperimentResults>, ParsingReport) {
+    let files = files
+        .iter()
+        .map(|f| (*f).clone())
+        .collect::<Vec<FileMetadata>>();
     let pb = ProgressBar::new(files.len() as u64);
 
-    for file in files {
-        let tx = tx.clone();
-        let s = file.filepath.to_owned();
-        let timestamp = file.timestamp;
-        pool.execute(move || {
-            if let Some(val) = parse_yaml(&s, timestamp) {
-                let nb_nodes = val.get_nb_nodes(OvhNodeFilter::All);
-                let nb_nodes_ovh = val.get_nb_nodes(OvhNodeFilter::Ovh);
-                let nb_nodes_external = val.get_nb_nodes(OvhNodeFilter::External);
-                let nb_links = val.get_nb_links(OvhNodeFilter::All);
-                let nb_links_external = val.get_nb_links(OvhNodeFilter::External);
-                let ecmp_diffs = val.get_ecmp_imbalance(OvhNodeFilter::All);
-                let ecmp_diffs_ovh = val.get_ecmp_imbalance(OvhNodeFilter::Ovh);
-                let ecmp_diffs_external = val.get_ecmp_imbalance(OvhNodeFilter::External);
-                let loads = val.get_link_loads(OvhNodeFilter::All);
-                let loads_ovh = val.get_link_loads(OvhNodeFilter::Ovh);
-                let loads_external = val.get_link_loads(OvhNodeFilter::External);
-                let nb_ecmp_links = val.get_nb_ecmp_links(OvhNodeFilter::All);
-                let nb_ecmp_links_ovh = val.get_nb_ecmp_links(OvhNodeFilter::Ovh);
-                let nb_ecmp_links_external = val.get_nb_ecmp_links(OvhNodeFilter::External);
-                // Easier, because we should divide by two for internal links, but by 1
-                // for peering links.
-                let nb_links_ovh = nb_links - nb_links_external;
-                tx.send(ExperimentResults {
-                    timestamp,
-                    nb_nodes,
-                    nb_nodes_ovh,
-                    nb_nodes_external,
-                    nb_links,
-                    nb_links_ovh,
-                    nb_links_external,
-                    ecmp_diffs,
-                    ecmp_diffs_ovh,
-                    ecmp_diffs_external,
-                    loads,
-                    loads_ovh,
-                    loads_external,
-                    nb_ecmp_links,
-                    nb_ecmp_links_ovh,
-                    nb_ecmp_links_external,
-                    // ..Default::default()  // Just in case we add other fields, the code compiles
-                })
-                .expect("Could not send data");
-            }
-        })
+    let store_cache = cache.clone();
+    let mut stream = SnapshotStream::with_task(&files, nb_threads, move |file| {
+        let parsed = match &cache {
+            Some(cache) => cache.parse(file),
+            None => file.parse(),
+        };
+        parsed.map(|val| experiment_results(&val))
+    });
+    if let Some(timeout) = timeout {
+        stream = stream.timeout(timeout);
     }
 
     let mut output: Vec<ExperimentResults> = Vec::with_capacity(files.len());
-    let timeout = Duration::new(10, 0);
-    for _ in 0..files.len() {
-        if let Ok(result) = rx.recv_timeout(timeout) {
-            output.push(result);
+    let mut report = ParsingReport::default();
+    for (file, outcome) in stream.outcomes() {
+        let outcome = match outcome {
+            TaskOutcome::Done(Ok(result)) => {
+                output.push(result);
+                FileOutcome::Ok
+            }
+            TaskOutcome::Done(Err(e)) => FileOutcome::ParseError(e.to_string()),
+            TaskOutcome::Panicked(msg) => FileOutcome::Panic(msg),
+            TaskOutcome::TimedOut => FileOutcome::Timeout,
+        };
+        if !matches!(outcome, FileOutcome::Ok) {
+            pb.println(format!(
+                "Could not parse {}: {} {}",
+                file.display_path(),
+                outcome.name(),
+                outcome.detail()
+            ));
+        }
+        for failure in store_cache.iter().flat_map(|c| c.take_store_failures()) {
+            pb.println(format!("Could not cache {}", failure));
         }
+        report.outcomes.push((file, outcome));
+        pb.inc(1);
     }
     pb.finish_with_message("done");
-    output.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-    output
+    (output, report)
 }
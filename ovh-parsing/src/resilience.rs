@@ -0,0 +1,486 @@
+//! Resilience of the OVH network to link and router failures.
+//!
+//! For each snapshot, the report lists:
+//! - the OVH routers, other than the border ones, with a single upstream adjacency, i.e., a
+//!   single neighbour closer (in hops) to the external peers, or none when they cannot reach
+//!   any peer;
+//! - the bridges between OVH routers, adjacencies whose failure splits the OVH network;
+//! - for each point of presence (PoP), the minimum number of links to cut to separate its
+//!   routers from the border routers of the other PoPs, all the parallel links counting.
+//!
+//! The reports are then grouped in time buckets to follow their evolution over the dataset.
+
+use crate::buckets::BucketUnit;
+use crate::graph::{Edge, Graph, NodeId};
+use crate::{pop_from_name, OvhData, OvhNodeFilter};
+use chrono::NaiveDateTime;
+use csv::Writer;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::Write;
+
+/// OVH router, not connected to external peers, with at most one upstream adjacency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SingleHomed {
+    pub router: String,
+    /// The only neighbour closer to the external peers, `None` when no peer can be reached.
+    pub upstream: Option<String>,
+}
+
+/// Adjacency between OVH routers whose failure splits the OVH network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bridge {
+    pub a: String,
+    pub b: String,
+    pub nb_links: usize,
+}
+
+/// Minimum cut between the routers of a PoP and the border routers of the other PoPs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PopCut {
+    pub pop: String,
+    pub nb_routers: usize,
+    /// Number of links of the cut, `None` without border routers in other PoPs.
+    pub cut: Option<usize>,
+    /// Adjacencies of one minimum cut.
+    pub adjacencies: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResilienceReport {
+    pub timestamp: NaiveDateTime,
+    pub single_homed: Vec<SingleHomed>,
+    pub bridges: Vec<Bridge>,
+    /// Sorted by PoP.
+    pub cuts: Vec<PopCut>,
+}
+
+impl ResilienceReport {
+    pub fn of(data: &OvhData) -> ResilienceReport {
+        ResilienceReport {
+            timestamp: data.timestamp,
+            single_homed: single_homed(data),
+            bridges: bridges(data),
+            cuts: pop_cuts(data),
+        }
+    }
+
+    /// The smallest cut of all the PoPs.
+    pub fn min_cut(&self) -> Option<usize> {
+        self.cuts.iter().filter_map(|c| c.cut).min()
+    }
+}
+
+fn single_homed(data: &OvhData) -> Vec<SingleHomed> {
+    let graph = Graph::from_data(data);
+    let peers = (0..graph.nb_nodes())
+        .filter(|&node| graph.node(node).external)
+        .collect::<Vec<NodeId>>();
+    let distances = graph.bfs_from(&peers);
+    let mut single_homed = (0..graph.nb_nodes())
+        // Border routers are one hop away from the peers
+        .filter(|&node| !graph.node(node).external && distances[node] != Some(1))
+        .filter_map(|node| {
+            let upstream = match distances[node] {
+                Some(distance) => graph
+                    .neighbors(node)
+                    .iter()
+                    .filter(|(neighbor, _)| distances[*neighbor] == Some(distance - 1))
+                    .map(|(neighbor, _)| graph.node(*neighbor).name.to_string())
+                    .collect::<Vec<String>>(),
+                None => Vec::new(),
+            };
+            match upstream.len() {
+                0 | 1 => Some(SingleHomed {
+                    router: graph.node(node).name.to_string(),
+                    upstream: upstream.into_iter().next(),
+                }),
+                _ => None,
+            }
+        })
+        .collect::<Vec<SingleHomed>>();
+    single_homed.sort_by(|a, b| a.router.cmp(&b.router));
+    single_homed
+}
+
+fn bridges(data: &OvhData) -> Vec<Bridge> {
+    let graph = Graph::from_data_with(data, OvhNodeFilter::Ovh);
+    let mut bridges = graph
+        .bridges()
+        .into_iter()
+        .map(|e| {
+            let edge = graph.edge(e);
+            let (a, b) = (&graph.node(edge.a).name, &graph.node(edge.b).name);
+            Bridge {
+                a: a.min(b).to_string(),
+                b: a.max(b).to_string(),
+                nb_links: edge.nb_links(),
+            }
+        })
+        .collect::<Vec<Bridge>>();
+    bridges.sort_by(|x, y| (&x.a, &x.b).cmp(&(&y.a, &y.b)));
+    bridges
+}
+
+fn pop_cuts(data: &OvhData) -> Vec<PopCut> {
+    let graph = Graph::from_data_with(data, OvhNodeFilter::Ovh);
+    let mut pops: BTreeMap<&str, Vec<NodeId>> = BTreeMap::new();
+    for (id, node) in graph.nodes().iter().enumerate() {
+        if let Some(pop) = pop_from_name(&node.name) {
+            pops.entry(pop).or_default().push(id);
+        }
+    }
+    let border = data
+        .get_border_routers()
+        .iter()
+        .filter_map(|r| graph.index_of(&r.name))
+        .collect::<Vec<NodeId>>();
+
+    pops.into_iter()
+        .map(|(pop, routers)| {
+            let sinks = border
+                .iter()
+                .copied()
+                .filter(|b| !routers.contains(b))
+                .collect::<Vec<NodeId>>();
+            let cut = match sinks.is_empty() {
+                true => None,
+                false => graph.min_edge_cut(&routers, &sinks, Edge::nb_links),
+            };
+            let adjacencies = cut
+                .iter()
+                .flat_map(|(_, edges)| edges.iter())
+                .map(|&e| {
+                    let edge = graph.edge(e);
+                    (
+                        graph.node(edge.a).name.to_string(),
+                        graph.node(edge.b).name.to_string(),
+                    )
+                })
+                .collect();
+            PopCut {
+                pop: pop.to_string(),
+                nb_routers: routers.len(),
+                cut: cut.map(|(value, _)| value),
+                adjacencies,
+            }
+        })
+        .collect()
+}
+
+/// Statistics of the minimum cut of a PoP over several snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CutStats {
+    pub nb_snapshots: usize,
+    pub min: usize,
+    pub max: usize,
+    pub sum: usize,
+}
+
+impl CutStats {
+    pub fn mean(&self) -> f64 {
+        self.sum as f64 / self.nb_snapshots as f64
+    }
+}
+
+/// Reports of the snapshots of a time bucket.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResilienceBucket {
+    pub nb_snapshots: usize,
+    /// Total number of single-homed routers and bridges, over all the snapshots.
+    pub total_single_homed: usize,
+    pub total_bridges: usize,
+    /// Number of snapshots in which each router is single-homed.
+    pub single_homed: BTreeMap<String, usize>,
+    /// Number of snapshots in which each adjacency is a bridge.
+    pub bridges: BTreeMap<(String, String), usize>,
+    pub cuts: BTreeMap<String, CutStats>,
+}
+
+/// Evolution of the resilience reports over time buckets.
+#[derive(Debug, Clone)]
+pub struct ResilienceHistory {
+    unit: BucketUnit,
+    buckets: BTreeMap<NaiveDateTime, ResilienceBucket>,
+}
+
+impl ResilienceHistory {
+    pub fn new(unit: BucketUnit) -> ResilienceHistory {
+        ResilienceHistory {
+            unit,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, report: &ResilienceReport) {
+        let bucket = self
+            .buckets
+            .entry(self.unit.start_of(report.timestamp))
+            .or_default();
+        bucket.nb_snapshots += 1;
+        bucket.total_single_homed += report.single_homed.len();
+        bucket.total_bridges += report.bridges.len();
+        for single_homed in report.single_homed.iter() {
+            *bucket
+                .single_homed
+                .entry(single_homed.router.to_string())
+                .or_default() += 1;
+        }
+        for bridge in report.bridges.iter() {
+            *bucket
+                .bridges
+                .entry((bridge.a.to_string(), bridge.b.to_string()))
+                .or_default() += 1;
+        }
+        for (pop, cut) in report
+            .cuts
+            .iter()
+            .filter_map(|c| c.cut.map(|cut| (&c.pop, cut)))
+        {
+            let stats = bucket.cuts.entry(pop.to_string()).or_insert(CutStats {
+                nb_snapshots: 0,
+                min: cut,
+                max: cut,
+                sum: 0,
+            });
+            stats.nb_snapshots += 1;
+            stats.min = stats.min.min(cut);
+            stats.max = stats.max.max(cut);
+            stats.sum += cut;
+        }
+    }
+
+    /// Buckets with at least one snapshot, by start.
+    pub fn buckets(&self) -> &BTreeMap<NaiveDateTime, ResilienceBucket> {
+        &self.buckets
+    }
+
+    /// All the buckets from the first to the last one, empty ones included.
+    fn all_buckets(&self) -> Vec<(NaiveDateTime, Option<&ResilienceBucket>)> {
+        let (first, last) = match (self.buckets.keys().next(), self.buckets.keys().last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return Vec::new(),
+        };
+        let mut all = Vec::new();
+        let mut start = first;
+        while start <= last {
+            all.push((start, self.buckets.get(&start)));
+            start = self.unit.next(start);
+        }
+        all
+    }
+}
+
+/// Writes one row per bucket as `timestamp,snapshots,single_homed,bridges,min_cut`, with
+/// headers, the mean numbers of single-homed routers and bridges per snapshot, and the
+/// smallest PoP cut. The timestamp is the start of the bucket, and the values of empty
+/// buckets are left empty.
+pub fn write_history_csv<W: Write>(
+    history: &ResilienceHistory,
+    wrt: W,
+) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record([
+        "timestamp",
+        "snapshots",
+        "single_homed",
+        "bridges",
+        "min_cut",
+    ])?;
+    for (start, bucket) in history.all_buckets() {
+        let timestamp = start.and_utc().timestamp().to_string();
+        match bucket {
+            Some(b) => wrt.write_record([
+                timestamp,
+                b.nb_snapshots.to_string(),
+                format!("{:.2}", b.total_single_homed as f64 / b.nb_snapshots as f64),
+                format!("{:.2}", b.total_bridges as f64 / b.nb_snapshots as f64),
+                b.cuts
+                    .values()
+                    .map(|c| c.min)
+                    .min()
+                    .map(|c| c.to_string())
+                    .unwrap_or_default(),
+            ])?,
+            None => {
+                wrt.write_record([timestamp, "0".to_string(), "".into(), "".into(), "".into()])?
+            }
+        }
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+/// Writes the single-homed routers of each bucket as `timestamp,router,snapshots,share`,
+/// with headers and the share of the snapshots of the bucket in percent.
+pub fn write_single_homed_csv<W: Write>(
+    history: &ResilienceHistory,
+    wrt: W,
+) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record(["timestamp", "router", "snapshots", "share"])?;
+    for (start, bucket) in history.buckets() {
+        for (router, count) in bucket.single_homed.iter() {
+            wrt.write_record([
+                start.and_utc().timestamp().to_string(),
+                router.to_string(),
+                count.to_string(),
+                format!("{:.2}", *count as f64 * 100.0 / bucket.nb_snapshots as f64),
+            ])?;
+        }
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+/// Writes the bridges of each bucket as `timestamp,router_a,router_b,snapshots,share`,
+/// with headers and the share of the snapshots of the bucket in percent.
+pub fn write_bridges_csv<W: Write>(
+    history: &ResilienceHistory,
+    wrt: W,
+) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record(["timestamp", "router_a", "router_b", "snapshots", "share"])?;
+    for (start, bucket) in history.buckets() {
+        for ((a, b), count) in bucket.bridges.iter() {
+            wrt.write_record([
+                start.and_utc().timestamp().to_string(),
+                a.to_string(),
+                b.to_string(),
+                count.to_string(),
+                format!("{:.2}", *count as f64 * 100.0 / bucket.nb_snapshots as f64),
+            ])?;
+        }
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+/// Writes the minimum cuts of each PoP and bucket as `timestamp,pop,snapshots,min,mean,max`,
+/// with headers.
+pub fn write_cuts_csv<W: Write>(history: &ResilienceHistory, wrt: W) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record(["timestamp", "pop", "snapshots", "min", "mean", "max"])?;
+    for (start, bucket) in history.buckets() {
+        for (pop, stats) in bucket.cuts.iter() {
+            wrt.write_record([
+                start.and_utc().timestamp().to_string(),
+                pop.to_string(),
+                stats.nb_snapshots.to_string(),
+                stats.min.to_string(),
+                format!("{:.2}", stats.mean()),
+                stats.max.to_string(),
+            ])?;
+        }
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Link, Router};
+    use chrono::{DateTime, Duration};
+    use std::collections::HashMap;
+
+    /// Two connected ams routers on AMS-IX, fra-1 behind them, then fra-2 and rbx-1 in a row,
+    /// and lon-1 on its own.
+    fn data(t: i64) -> OvhData {
+        let mut data: HashMap<String, Router> = HashMap::new();
+        for (router, peer, label) in [
+            ("ams-1", "AMS-IX", "#1"),
+            ("ams-2", "AMS-IX", "#1"),
+            ("ams-1", "ams-2", "#1"),
+            ("ams-1", "fra-1", "#1"),
+            ("ams-1", "fra-1", "#2"),
+            ("ams-2", "fra-1", "#1"),
+            ("fra-1", "fra-2", "#1"),
+            ("fra-2", "rbx-1", "#1"),
+        ] {
+            data.entry(router.to_string())
+                .or_insert_with(|| Router {
+                    name: router.to_string(),
+                    peers: HashMap::new(),
+                })
+                .peers
+                .entry(peer.to_string())
+                .or_default()
+                .push(Link {
+                    label: label.to_string(),
+                    load: 10,
+                });
+        }
+        data.insert(
+            "lon-1".to_string(),
+            Router {
+                name: "lon-1".to_string(),
+                peers: HashMap::new(),
+            },
+        );
+        OvhData {
+            timestamp: DateTime::from_timestamp(t, 0).unwrap().naive_utc(),
+            data,
+        }
+    }
+
+    #[test]
+    fn test_resilience() {
+        let report = ResilienceReport::of(&data(0));
+        let single_homed = report
+            .single_homed
+            .iter()
+            .map(|s| (s.router.as_str(), s.upstream.as_deref()))
+            .collect::<Vec<(&str, Option<&str>)>>();
+        assert_eq!(
+            single_homed,
+            vec![
+                ("fra-2", Some("fra-1")),
+                ("lon-1", None),
+                ("rbx-1", Some("fra-2"))
+            ]
+        );
+        let bridges = report
+            .bridges
+            .iter()
+            .map(|b| (b.a.as_str(), b.b.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+        assert_eq!(bridges, vec![("fra-1", "fra-2"), ("fra-2", "rbx-1")]);
+
+        let cuts = report
+            .cuts
+            .iter()
+            .map(|c| (c.pop.as_str(), c.nb_routers, c.cut))
+            .collect::<Vec<(&str, usize, Option<usize>)>>();
+        // The border routers are all in ams, and both links between ams-1 and fra-1 count
+        assert_eq!(
+            cuts,
+            vec![
+                ("ams", 2, None),
+                ("fra", 2, Some(3)),
+                ("lon", 1, Some(0)),
+                ("rbx", 1, Some(1))
+            ]
+        );
+        assert_eq!(report.cuts[1].adjacencies.len(), 2);
+        assert_eq!(report.min_cut(), Some(0));
+
+        let mut history = ResilienceHistory::new(BucketUnit::Month);
+        let month = Duration::days(31).num_seconds();
+        for t in [0, 300, 2 * month] {
+            history.push(&ResilienceReport::of(&data(t)));
+        }
+        assert_eq!(history.buckets().len(), 2);
+        let first = history.buckets().values().next().unwrap();
+        assert_eq!((first.nb_snapshots, first.single_homed["rbx-1"]), (2, 2));
+        assert_eq!(first.cuts["fra"].mean(), 3.0);
+
+        let mut csv = Vec::new();
+        write_history_csv(&history, &mut csv).unwrap();
+        // The empty month in between is kept
+        assert_eq!(
+            String::from_utf8(csv).unwrap().lines().nth(2),
+            Some("2678400,0,,,")
+        );
+    }
+}
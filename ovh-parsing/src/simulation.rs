@@ -0,0 +1,546 @@
+//! What-if simulation of failures on a snapshot.
+//!
+//! Routers, adjacencies or individual links are removed from the snapshot, and the load
+//! they carried is moved onto the rest of the network:
+//! - the load of a removed link goes to the surviving parallel links of the same adjacency
+//!   and direction, shared equally (ECMP);
+//! - when no parallel link survives, it is re-routed from the router to the peer along the
+//!   shortest paths (in hops) of the remaining network, split equally at each router between
+//!   the next hops and between the parallel links towards each of them. External peers can
+//!   be the end of a path, but never carry traffic between two other routers;
+//! - the traffic entering a failed router is assumed to leave it like its observed outgoing
+//!   traffic, and is re-routed towards its other neighbours in the same proportions, while
+//!   the traffic it originates is lost.
+//!
+//! Loads are percentages, so all the links are assumed to have the same capacity.
+
+use crate::graph::Graph;
+use crate::{Link, OvhData, Router};
+use csv::Writer;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+/// A failure, written `router`, `router:peer` for all the links between two routers,
+/// or `router:peer:label` for the links with this label between them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    Router(String),
+    Adjacency(String, String),
+    Link(String, String, String),
+}
+
+impl Failure {
+    fn removes_router(&self, name: &str) -> bool {
+        matches!(self, Failure::Router(router) if router == name)
+    }
+
+    /// Whether the link `link` listed by `router` towards `peer` goes down.
+    fn removes_link(&self, router: &str, peer: &str, link: &Link) -> bool {
+        let between = |a: &str, b: &str| (a == router && b == peer) || (a == peer && b == router);
+        match self {
+            Failure::Router(name) => name == router || name == peer,
+            Failure::Adjacency(a, b) => between(a, b),
+            Failure::Link(a, b, label) => between(a, b) && &link.label == label,
+        }
+    }
+}
+
+impl FromStr for Failure {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Failure, String> {
+        let parts = s.split(':').collect::<Vec<&str>>();
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(format!("Empty router, peer or label in failure: {}", s));
+        }
+        match parts[..] {
+            [router] => Ok(Failure::Router(router.to_string())),
+            [a, b] => Ok(Failure::Adjacency(a.to_string(), b.to_string())),
+            [a, b, label] => Ok(Failure::Link(
+                a.to_string(),
+                b.to_string(),
+                label.to_string(),
+            )),
+            _ => Err(format!(
+                "Expected <router>, <router>:<peer> or <router>:<peer>:<label>, got {}",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Router(router) => write!(f, "{}", router),
+            Failure::Adjacency(a, b) => write!(f, "{}:{}", a, b),
+            Failure::Link(a, b, label) => write!(f, "{}:{}:{}", a, b, label),
+        }
+    }
+}
+
+/// The snapshot without the failed routers and links.
+pub fn apply(data: &OvhData, failures: &[Failure]) -> OvhData {
+    let failed = data
+        .data
+        .values()
+        .filter(|router| !failures.iter().any(|f| f.removes_router(&router.name)))
+        .map(|router| {
+            let peers = router
+                .peers
+                .iter()
+                .map(|(peer, links)| {
+                    let links = links
+                        .iter()
+                        .filter(|link| {
+                            !failures
+                                .iter()
+                                .any(|f| f.removes_link(&router.name, peer, link))
+                        })
+                        .cloned()
+                        .collect::<Vec<Link>>();
+                    (peer.to_string(), links)
+                })
+                .filter(|(_, links)| !links.is_empty())
+                .collect();
+            (
+                router.name.to_string(),
+                Router {
+                    name: router.name.to_string(),
+                    peers,
+                },
+            )
+        })
+        .collect();
+    OvhData {
+        timestamp: data.timestamp,
+        data: failed,
+    }
+}
+
+/// Estimated load of the link `label` from `router` towards `peer` after the failures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkEstimate {
+    pub router: String,
+    pub peer: String,
+    pub label: String,
+    /// Observed load, 0 if the link was not listed in this direction.
+    pub before: u32,
+    pub after: f64,
+}
+
+impl LinkEstimate {
+    pub fn is_overloaded(&self) -> bool {
+        self.after > 100.0
+    }
+}
+
+/// Traffic that cannot reach its destination after the failures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unrouted {
+    pub from: String,
+    pub to: String,
+    /// Load (%) of one link.
+    pub load: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationReport {
+    pub failures: Vec<Failure>,
+    /// OVH routers connected to an external peer before the failures, but not after.
+    pub disconnected: Vec<String>,
+    /// Links whose load changes, sorted by router, peer and label.
+    pub links: Vec<LinkEstimate>,
+    pub unrouted: Vec<Unrouted>,
+}
+
+impl SimulationReport {
+    /// Links whose estimated load exceeds 100%.
+    pub fn overloaded(&self) -> Vec<&LinkEstimate> {
+        self.links.iter().filter(|l| l.is_overloaded()).collect()
+    }
+}
+
+/// OVH routers of `graph` connected to one of the `peering` routers.
+fn connected_to_peers(graph: &Graph, peering: &[&str]) -> HashSet<String> {
+    let mut seen = vec![false; graph.nb_nodes()];
+    let mut queue = peering
+        .iter()
+        .filter_map(|name| graph.index_of(name))
+        .collect::<VecDeque<usize>>();
+    queue.iter().for_each(|&node| seen[node] = true);
+    while let Some(node) = queue.pop_front() {
+        for &(neighbor, _) in graph.neighbors(node) {
+            if !seen[neighbor] {
+                seen[neighbor] = true;
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    (0..graph.nb_nodes())
+        .filter(|&node| seen[node] && !graph.node(node).external)
+        .map(|node| graph.node(node).name.to_string())
+        .collect()
+}
+
+struct Redistribution<'a> {
+    graph: Graph,
+    failed: &'a OvhData,
+    added: HashMap<(String, String, String), f64>,
+    unrouted: Vec<Unrouted>,
+}
+
+impl Redistribution<'_> {
+    /// Shares `load` equally between `links`, listed by `router` towards `peer`.
+    fn spread(&mut self, router: &str, peer: &str, labels: &[String], load: f64) {
+        for label in labels {
+            *self
+                .added
+                .entry((router.to_string(), peer.to_string(), label.to_string()))
+                .or_default() += load / labels.len() as f64;
+        }
+    }
+
+    /// Sends `load` from `from` to `to` along the shortest paths.
+    fn route(&mut self, from: &str, to: &str, load: f64) {
+        let unrouted = Unrouted {
+            from: from.to_string(),
+            to: to.to_string(),
+            load,
+        };
+        let (source, target) = match (self.graph.index_of(from), self.graph.index_of(to)) {
+            (Some(source), Some(target)) => (source, target),
+            _ => return self.unrouted.push(unrouted),
+        };
+        // The external peers do not carry the traffic between OVH routers
+        let transit = (0..self.graph.nb_nodes())
+            .map(|node| node == target || !self.graph.node(node).external)
+            .collect::<Vec<bool>>();
+        let distances = self.graph.bfs_through(&[target], |node| transit[node]);
+        if distances[source].is_none() {
+            return self.unrouted.push(unrouted);
+        }
+
+        // Pushes the flow hop by hop towards the target, the farthest routers first
+        let mut flows: HashMap<usize, f64> = HashMap::from([(source, load)]);
+        let mut order = vec![source];
+        while let Some(node) = order.pop() {
+            let flow = match flows.remove(&node) {
+                Some(flow) if node != target => flow,
+                _ => continue,
+            };
+            let distance = distances[node].unwrap();
+            let next_hops = self
+                .graph
+                .neighbors(node)
+                .iter()
+                .filter(|&&(neighbor, _)| {
+                    distances[neighbor] == Some(distance - 1) && transit[neighbor]
+                })
+                .copied()
+                .collect::<Vec<(usize, usize)>>();
+            for (next, edge) in next_hops.iter().copied() {
+                let edge = self.graph.edge(edge);
+                // Links listed in this direction, or else in the other one
+                let links = match edge.links_from(node) {
+                    [] => edge.links_from(next),
+                    links => links,
+                };
+                let labels = links
+                    .iter()
+                    .map(|l| l.label.to_string())
+                    .collect::<Vec<String>>();
+                let (router, peer) = (
+                    self.graph.node(node).name.to_string(),
+                    self.graph.node(next).name.to_string(),
+                );
+                let share = flow / next_hops.len() as f64;
+                self.spread(&router, &peer, &labels, share);
+                if !flows.contains_key(&next) {
+                    order.push(next);
+                }
+                *flows.entry(next).or_default() += share;
+            }
+            // Routers closer to the target come last, once all their flow is known
+            order.sort_by_key(|&n| distances[n]);
+        }
+    }
+
+    /// Moves the load of a removed link listed by `router` towards `peer`.
+    fn redistribute(&mut self, router: &str, peer: &str, load: f64) {
+        let siblings = self
+            .failed
+            .data
+            .get(router)
+            .and_then(|r| r.peers.get(peer))
+            .map(|links| {
+                links
+                    .iter()
+                    .map(|l| l.label.to_string())
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
+        if siblings.is_empty() {
+            self.route(router, peer, load);
+        } else {
+            self.spread(router, peer, &siblings, load);
+        }
+    }
+}
+
+/// Estimates the effect of `failures` on `data`.
+pub fn simulate(data: &OvhData, failures: &[Failure]) -> SimulationReport {
+    let failed = apply(data, failures);
+    let peering = data
+        .get_peering_routers()
+        .iter()
+        .map(|r| r.name.as_str())
+        .collect::<Vec<&str>>();
+    let connected_before = connected_to_peers(&Graph::from_data(data), &peering);
+    let graph = Graph::from_data(&failed);
+    let connected_after = connected_to_peers(&graph, &peering);
+    let mut disconnected = connected_before
+        .difference(&connected_after)
+        .filter(|name| failed.data.contains_key(*name))
+        .cloned()
+        .collect::<Vec<String>>();
+    disconnected.sort();
+
+    let is_failed_router = |name: &str| failures.iter().any(|f| f.removes_router(name));
+    let mut redistribution = Redistribution {
+        graph,
+        failed: &failed,
+        added: HashMap::new(),
+        unrouted: Vec::new(),
+    };
+    let mut routers = data.data.values().collect::<Vec<&Router>>();
+    routers.sort_by(|a, b| a.name.cmp(&b.name));
+    for router in routers.into_iter().filter(|r| !is_failed_router(&r.name)) {
+        let mut peers = router.peers.iter().collect::<Vec<(&String, &Vec<Link>)>>();
+        peers.sort_by_key(|(peer, _)| *peer);
+        for (peer, links) in peers {
+            let removed = links
+                .iter()
+                .filter(|link| {
+                    failures
+                        .iter()
+                        .any(|f| f.removes_link(&router.name, peer, link))
+                })
+                .map(|link| link.load as f64)
+                .sum::<f64>();
+            if removed == 0.0 {
+                continue;
+            }
+            if !is_failed_router(peer) {
+                redistribution.redistribute(&router.name, peer, removed);
+                continue;
+            }
+            // The traffic crossing the failed router leaves it like its outgoing traffic
+            let exits = data
+                .data
+                .get(peer.as_str())
+                .map(|failed_router| {
+                    failed_router
+                        .peers
+                        .iter()
+                        .filter(|(next, _)| *next != &router.name && !is_failed_router(next))
+                        .map(|(next, links)| {
+                            (next.to_string(), links.iter().map(|l| l.load as f64).sum())
+                        })
+                        .collect::<Vec<(String, f64)>>()
+                })
+                .unwrap_or_default();
+            let total = exits.iter().map(|(_, load)| load).sum::<f64>();
+            if exits.is_empty() {
+                redistribution.unrouted.push(Unrouted {
+                    from: router.name.to_string(),
+                    to: peer.to_string(),
+                    load: removed,
+                });
+            }
+            for (next, load) in exits.iter() {
+                let share = match total {
+                    t if t > 0.0 => load / t,
+                    _ => 1.0 / exits.len() as f64,
+                };
+                redistribution.route(&router.name, next, removed * share);
+            }
+        }
+    }
+
+    let mut links = redistribution
+        .added
+        .into_iter()
+        .map(|((router, peer, label), added)| {
+            let before = failed
+                .data
+                .get(&router)
+                .and_then(|r| r.peers.get(&peer))
+                .and_then(|links| {
+                    links
+                        .iter()
+                        .filter(|l| l.label == label)
+                        .map(|l| l.load)
+                        .max()
+                })
+                .unwrap_or_default();
+            LinkEstimate {
+                router,
+                peer,
+                label,
+                before,
+                after: before as f64 + added,
+            }
+        })
+        .collect::<Vec<LinkEstimate>>();
+    links.sort_by(|a, b| (&a.router, &a.peer, &a.label).cmp(&(&b.router, &b.peer, &b.label)));
+
+    SimulationReport {
+        failures: failures.to_vec(),
+        disconnected,
+        links,
+        unrouted: redistribution.unrouted,
+    }
+}
+
+/// Writes the estimated loads as `router,peer,label,load_before,load_after,overloaded`, with headers.
+pub fn write_estimates_csv<W: Write>(links: &[LinkEstimate], wrt: W) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record([
+        "router",
+        "peer",
+        "label",
+        "load_before",
+        "load_after",
+        "overloaded",
+    ])?;
+    for link in links {
+        wrt.write_record([
+            link.router.to_string(),
+            link.peer.to_string(),
+            link.label.to_string(),
+            link.before.to_string(),
+            format!("{:.2}", link.after),
+            link.is_overloaded().to_string(),
+        ])?;
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    /// Two paths from a to the exchange IX: a-b-IX and a-c-d-IX, and an unused path b-f-d.
+    fn data() -> OvhData {
+        let mut data: HashMap<String, Router> = HashMap::new();
+        for (router, peer, label, load) in [
+            ("a", "b", "#1", 40),
+            ("a", "b", "#2", 40),
+            ("b", "IX", "#1", 70),
+            ("a", "c", "#1", 30),
+            ("c", "d", "#1", 30),
+            ("d", "IX", "#1", 30),
+            ("e", "b", "#1", 10),
+            ("IX", "b", "#1", 5),
+            ("b", "f", "#1", 0),
+            ("f", "d", "#1", 0),
+        ] {
+            data.entry(router.to_string())
+                .or_insert_with(|| Router {
+                    name: router.to_string(),
+                    peers: HashMap::new(),
+                })
+                .peers
+                .entry(peer.to_string())
+                .or_default()
+                .push(Link {
+                    label: label.to_string(),
+                    load,
+                });
+        }
+        OvhData {
+            timestamp: DateTime::from_timestamp(0, 0).unwrap().naive_utc(),
+            data,
+        }
+    }
+
+    #[test]
+    fn test_simulation() {
+        let estimate = |report: &SimulationReport, router: &str, peer: &str, label: &str| {
+            report
+                .links
+                .iter()
+                .find(|l| l.router == router && l.peer == peer && l.label == label)
+                .map(|l| l.after)
+        };
+
+        // The load of a link goes to its parallel link
+        let report = simulate(&data(), &["a:b:#2".parse().unwrap()]);
+        assert_eq!(estimate(&report, "a", "b", "#1"), Some(80.0));
+        assert!(report.overloaded().is_empty());
+        assert!(report.disconnected.is_empty());
+
+        // Without b, its traffic from a goes through c and d, and e is cut from the peers
+        let report = simulate(&data(), &["b".parse().unwrap()]);
+        assert_eq!(report.disconnected, vec!["e".to_string()]);
+        assert_eq!(estimate(&report, "a", "c", "#1"), Some(110.0));
+        assert_eq!(estimate(&report, "c", "d", "#1"), Some(110.0));
+        assert_eq!(estimate(&report, "d", "IX", "#1"), Some(110.0));
+        assert_eq!(report.overloaded().len(), 3);
+        // The traffic from e to b, and from IX to b, has nowhere to go
+        assert_eq!(report.unrouted.len(), 2);
+
+        // Without the a-c adjacency, its traffic goes through b and f, not through the exchange
+        let report = simulate(&data(), &["a:c".parse().unwrap()]);
+        assert_eq!(estimate(&report, "a", "b", "#1"), Some(55.0));
+        assert_eq!(estimate(&report, "b", "f", "#1"), Some(30.0));
+        assert_eq!(estimate(&report, "d", "c", "#1"), Some(30.0));
+        // Only the links given more traffic are reported
+        assert_eq!(estimate(&report, "b", "IX", "#1"), None);
+        assert!(report.overloaded().is_empty());
+        assert!(report.unrouted.is_empty());
+        // Without b-f, a and c are only connected through the exchange
+        let report = simulate(&data(), &["a:c".parse().unwrap(), "b:f".parse().unwrap()]);
+        assert!(report.links.is_empty());
+        assert_eq!(report.unrouted.len(), 1);
+        assert_eq!(
+            (
+                report.unrouted[0].from.as_str(),
+                report.unrouted[0].to.as_str()
+            ),
+            ("a", "c")
+        );
+        let report = simulate(
+            &data(),
+            &["a:c".parse().unwrap(), "a:b:#1".parse().unwrap()],
+        );
+        let overloaded = report.overloaded();
+        assert_eq!(overloaded.len(), 1);
+        assert_eq!(
+            (overloaded[0].label.as_str(), overloaded[0].after),
+            ("#2", 110.0)
+        );
+    }
+
+    #[test]
+    fn test_parse_failure() {
+        assert_eq!("a".parse::<Failure>(), Ok(Failure::Router("a".to_string())));
+        assert_eq!(
+            "a:b:#1".parse::<Failure>(),
+            Ok(Failure::Link(
+                "a".to_string(),
+                "b".to_string(),
+                "#1".to_string()
+            ))
+        );
+        for s in ["", "a:", ":b", "a::", "a:b:", "a:b:#1:x"] {
+            assert!(s.parse::<Failure>().is_err(), "{}", s);
+        }
+    }
+}
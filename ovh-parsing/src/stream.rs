@@ -0,0 +1,323 @@
+//! Parallel parsing of snapshots, yielded one by one in timestamp order.
+//!
+//! Only a bounded number of snapshots are being parsed or waiting to be yielded at
+//! any time, so that an analysis can go through the whole dataset in constant memory:
+//!
+//! ```no_run
+//! use ovh_parsing::{get_files::get_all_ovh_files, stream::SnapshotStream};
+//!
+//! let files = get_all_ovh_files("data").unwrap();
+//! for parsed in SnapshotStream::new(&files, 4, None) {
+//!     match parsed {
+//!         Ok(data) => println!("{}: {} routers", data.timestamp, data.data.len()),
+//!         Err(e) => println!("Parsing error: {}", e),
+//!     }
+//! }
+//! ```
+
+use crate::{FileMetadata, OvhData, ParseError, SnapshotCache};
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use threadpool::ThreadPool;
+
+/// Number of snapshots in flight per parsing thread, by default.
+const BUFFER_PER_THREAD: usize = 4;
+
+type Task<T> = Arc<dyn Fn(&FileMetadata) -> T + Send + Sync>;
+
+/// Sent by the workers: when they start a task, then its result
+/// or the message of the panic that interrupted it.
+enum Message<T> {
+    Started(Instant),
+    Finished(Result<T, String>),
+}
+
+/// What happened to the task of one file.
+#[derive(Debug)]
+pub enum TaskOutcome<T> {
+    Done(T),
+    /// The task panicked, with this message.
+    Panicked(String),
+    /// The task did not finish within the timeout. It keeps its thread busy until it ends,
+    /// but its result is dropped.
+    TimedOut,
+}
+
+/// Runs a task on each file in parallel, and yields the results in timestamp order.
+/// By default the task parses the snapshot, through the cache if one is given.
+pub struct SnapshotStream<T = Result<OvhData, ParseError>> {
+    files: Vec<FileMetadata>,
+    task: Task<T>,
+    pool: ThreadPool,
+    tx: Sender<(usize, Message<T>)>,
+    rx: Receiver<(usize, Message<T>)>,
+    /// Maximum number of files dispatched but not yet yielded.
+    buffer: usize,
+    /// Maximum running time of a task, if any.
+    timeout: Option<Duration>,
+    next_dispatch: usize,
+    next_yield: usize,
+    /// Start time of the tasks that have not been yielded yet.
+    started: BTreeMap<usize, Instant>,
+    /// Results received ahead of their turn.
+    pending: BTreeMap<usize, Result<T, String>>,
+}
+
+impl SnapshotStream {
+    /// Parses `files` with `nb_threads` threads, using `cache` when given.
+    pub fn new(
+        files: &[FileMetadata],
+        nb_threads: usize,
+        cache: Option<SnapshotCache>,
+    ) -> SnapshotStream {
+        SnapshotStream::with_task(files, nb_threads, move |file| match &cache {
+            Some(cache) => cache.parse(file),
+            None => file.parse(),
+        })
+    }
+}
+
+impl<T: Send + 'static> SnapshotStream<T> {
+    /// Runs `task` on each of the `files` with `nb_threads` threads.
+    pub fn with_task<F>(files: &[FileMetadata], nb_threads: usize, task: F) -> SnapshotStream<T>
+    where
+        F: Fn(&FileMetadata) -> T + Send + Sync + 'static,
+    {
+        let nb_threads = nb_threads.max(1);
+        let mut files = files.to_vec();
+        files.sort_by_key(|f| f.timestamp);
+        let (tx, rx) = channel();
+        SnapshotStream {
+            files,
+            task: Arc::new(task),
+            pool: ThreadPool::new(nb_threads),
+            tx,
+            rx,
+            buffer: nb_threads * BUFFER_PER_THREAD,
+            timeout: None,
+            next_dispatch: 0,
+            next_yield: 0,
+            started: BTreeMap::new(),
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the maximum number of snapshots being parsed or waiting to be yielded.
+    pub fn buffer(mut self, buffer: usize) -> SnapshotStream<T> {
+        self.buffer = buffer.max(1);
+        self
+    }
+
+    /// Gives up on a task that runs for longer than `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> SnapshotStream<T> {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Yields each file along with the outcome of its task, instead of panicking
+    /// when a task panicked or timed out.
+    pub fn outcomes(self) -> Outcomes<T> {
+        Outcomes { stream: self }
+    }
+
+    /// Files handled by the stream, in the order of the results.
+    pub fn files(&self) -> &[FileMetadata] {
+        &self.files
+    }
+
+    fn dispatch(&mut self) {
+        while self.next_dispatch < self.files.len()
+            && self.next_dispatch - self.next_yield < self.buffer
+        {
+            let idx = self.next_dispatch;
+            let file = self.files[idx].clone();
+            let task = self.task.clone();
+            let tx = self.tx.clone();
+            self.pool.execute(move || {
+                // The receiver is gone if the stream was dropped early
+                let _ = tx.send((idx, Message::Started(Instant::now())));
+                let result = panic::catch_unwind(AssertUnwindSafe(|| task(&file)))
+                    .map_err(|e| panic_message(e.as_ref()));
+                let _ = tx.send((idx, Message::Finished(result)));
+            });
+            self.next_dispatch += 1;
+        }
+    }
+
+    fn receive(&mut self, idx: usize, message: Message<T>) {
+        match message {
+            // Results of the tasks that timed out are dropped
+            _ if idx < self.next_yield => {}
+            Message::Started(at) => {
+                self.started.insert(idx, at);
+            }
+            Message::Finished(result) => {
+                self.started.remove(&idx);
+                self.pending.insert(idx, result);
+            }
+        }
+    }
+
+    /// Waits for the task of the next file, and returns the file with its outcome.
+    fn next_outcome(&mut self) -> Option<(FileMetadata, TaskOutcome<T>)> {
+        if self.next_yield >= self.files.len() {
+            return None;
+        }
+        self.dispatch();
+        let outcome = loop {
+            if let Some(result) = self.pending.remove(&self.next_yield) {
+                break match result {
+                    Ok(value) => TaskOutcome::Done(value),
+                    Err(msg) => TaskOutcome::Panicked(msg),
+                };
+            }
+            let deadline = match (self.timeout, self.started.get(&self.next_yield)) {
+                (Some(timeout), Some(&start)) => Some(start + timeout),
+                _ => None,
+            };
+            // The stream holds a sender, so the channel never disconnects
+            let received = match deadline {
+                Some(deadline) => {
+                    let wait = deadline.saturating_duration_since(Instant::now());
+                    self.rx.recv_timeout(wait).ok()
+                }
+                None => Some(self.rx.recv().expect("Parsing channel closed")),
+            };
+            match received {
+                Some((idx, message)) => self.receive(idx, message),
+                None => {
+                    self.started.remove(&self.next_yield);
+                    break TaskOutcome::TimedOut;
+                }
+            }
+        };
+        let file = self.files[self.next_yield].clone();
+        self.next_yield += 1;
+        Some((file, outcome))
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+impl<T: Send + 'static> Iterator for SnapshotStream<T> {
+    type Item = T;
+
+    /// Panics if the task panicked or timed out on the next file.
+    fn next(&mut self) -> Option<T> {
+        let (file, outcome) = self.next_outcome()?;
+        match outcome {
+            TaskOutcome::Done(value) => Some(value),
+            TaskOutcome::Panicked(msg) => {
+                panic!("Task panicked on {}: {}", file.display_path(), msg)
+            }
+            TaskOutcome::TimedOut => panic!("Task timed out on {}", file.display_path()),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.files.len() - self.next_yield;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T: Send + 'static> ExactSizeIterator for SnapshotStream<T> {}
+
+/// Each file of a `SnapshotStream` with the outcome of its task, in timestamp order.
+pub struct Outcomes<T> {
+    stream: SnapshotStream<T>,
+}
+
+impl<T: Send + 'static> Iterator for Outcomes<T> {
+    type Item = (FileMetadata, TaskOutcome<T>);
+
+    fn next(&mut self) -> Option<(FileMetadata, TaskOutcome<T>)> {
+        self.stream.next_outcome()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+impl<T: Send + 'static> ExactSizeIterator for Outcomes<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn file(t: i64) -> FileMetadata {
+        FileMetadata {
+            filepath: format!("europe_{}.yaml", t),
+            timestamp: DateTime::from_timestamp(t, 0).unwrap().naive_utc(),
+            member: None,
+        }
+    }
+
+    #[test]
+    fn test_ordered_and_bounded() {
+        // Unsorted input, and later files finish first
+        let files = (0..20).rev().map(file).collect::<Vec<FileMetadata>>();
+        let started = Arc::new(AtomicUsize::new(0));
+        let counter = started.clone();
+        let stream = SnapshotStream::with_task(&files, 4, move |f| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let t = f.timestamp.and_utc().timestamp();
+            std::thread::sleep(Duration::from_millis(20 - t as u64));
+            t
+        })
+        .buffer(6);
+        assert_eq!(stream.len(), 20);
+
+        let mut yielded = Vec::new();
+        for t in stream {
+            yielded.push(t);
+            assert!(started.load(Ordering::SeqCst) <= yielded.len() + 6);
+        }
+        assert_eq!(yielded, (0..20).collect::<Vec<i64>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "europe_3.yaml")]
+    fn test_panic_is_forwarded() {
+        let files = (0..5).map(file).collect::<Vec<FileMetadata>>();
+        SnapshotStream::with_task(&files, 2, |f| {
+            if f.timestamp.and_utc().timestamp() == 3 {
+                panic!("boom");
+            }
+        })
+        .for_each(drop);
+    }
+
+    #[test]
+    fn test_outcomes() {
+        let files = (0..4).map(file).collect::<Vec<FileMetadata>>();
+        let outcomes =
+            SnapshotStream::with_task(&files, 2, |f| match f.timestamp.and_utc().timestamp() {
+                1 => panic!("boom"),
+                2 => std::thread::sleep(Duration::from_millis(500)),
+                _ => {}
+            })
+            .timeout(Duration::from_millis(100))
+            .outcomes()
+            .map(|(f, outcome)| (f.timestamp.and_utc().timestamp(), outcome))
+            .collect::<Vec<(i64, TaskOutcome<()>)>>();
+        assert!(matches!(outcomes[0], (0, TaskOutcome::Done(()))));
+        assert!(matches!(&outcomes[1], (1, TaskOutcome::Panicked(msg)) if msg == "boom"));
+        assert!(matches!(outcomes[2], (2, TaskOutcome::TimedOut)));
+        assert!(matches!(outcomes[3], (3, TaskOutcome::Done(()))));
+    }
+}
@@ -0,0 +1,182 @@
+//! Summary statistics (quantiles, mean, standard deviation) of the loads, ECMP diffs
+//! and ECMP group sizes, per time bucket.
+
+use crate::buckets::Bucket;
+use crate::{ExperimentResults, OvhNodeFilter};
+use csv::Writer;
+use std::error::Error;
+use std::io::Write;
+
+/// The values of the results of a snapshot that are summarized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Load,
+    EcmpDiff,
+    EcmpSize,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 3] = [Metric::Load, Metric::EcmpDiff, Metric::EcmpSize];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Metric::Load => "load",
+            Metric::EcmpDiff => "ecmp_diff",
+            Metric::EcmpSize => "ecmp_size",
+        }
+    }
+
+    pub fn values_of<'a>(&self, exp: &'a ExperimentResults, ovh_nodes: OvhNodeFilter) -> &'a [i8] {
+        match self {
+            Metric::Load => exp.loads_of(ovh_nodes),
+            Metric::EcmpDiff => exp.ecmp_diffs_of(ovh_nodes),
+            Metric::EcmpSize => exp.nb_ecmp_links_of(ovh_nodes),
+        }
+    }
+}
+
+/// Distribution of a non-empty set of values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub min: f64,
+    pub p5: f64,
+    pub p25: f64,
+    pub median: f64,
+    pub p75: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Population standard deviation.
+    pub stddev: f64,
+}
+
+/// Quantile `q` of sorted values, interpolated linearly between the closest ranks.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let rank = q * (sorted.len() - 1) as f64;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f64)
+}
+
+impl Summary {
+    /// Summary of the values, `None` if there are none.
+    pub fn of<I>(values: I) -> Option<Summary>
+    where
+        I: IntoIterator,
+        I::Item: Into<f64>,
+    {
+        let mut sorted = values.into_iter().map(Into::into).collect::<Vec<f64>>();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let count = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / count as f64;
+        let variance = sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+        Some(Summary {
+            count,
+            min: sorted[0],
+            p5: quantile(&sorted, 0.05),
+            p25: quantile(&sorted, 0.25),
+            median: quantile(&sorted, 0.5),
+            p75: quantile(&sorted, 0.75),
+            p95: quantile(&sorted, 0.95),
+            p99: quantile(&sorted, 0.99),
+            max: sorted[count - 1],
+            mean,
+            stddev: variance.sqrt(),
+        })
+    }
+}
+
+/// Writes one row per bucket, metric and kind of routers as
+/// `timestamp,metric,routers,count,min,p5,p25,median,p75,p95,p99,max,mean,stddev`, with headers.
+/// The timestamp is the start of the bucket, and the statistics of empty buckets are left empty.
+pub fn write_summaries_csv<W: Write>(
+    aggr: &[Bucket<&ExperimentResults>],
+    wrt: W,
+) -> Result<(), Box<dyn Error>> {
+    let all_routers_options = [
+        (OvhNodeFilter::All, "all"),
+        (OvhNodeFilter::Ovh, "ovh"),
+        (OvhNodeFilter::External, "external"),
+    ];
+
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record([
+        "timestamp",
+        "metric",
+        "routers",
+        "count",
+        "min",
+        "p5",
+        "p25",
+        "median",
+        "p75",
+        "p95",
+        "p99",
+        "max",
+        "mean",
+        "stddev",
+    ])?;
+    for bucket in aggr {
+        let timestamp = bucket.start.and_utc().timestamp().to_string();
+        for metric in Metric::ALL {
+            for (option, routers) in all_routers_options {
+                let values = bucket
+                    .values
+                    .iter()
+                    .flat_map(|&exp| metric.values_of(exp, option).iter().copied());
+                let mut record = vec![
+                    timestamp.clone(),
+                    metric.as_str().to_string(),
+                    routers.to_string(),
+                ];
+                match Summary::of(values) {
+                    Some(s) => {
+                        record.push(s.count.to_string());
+                        record.extend(
+                            [
+                                s.min, s.p5, s.p25, s.median, s.p75, s.p95, s.p99, s.max, s.mean,
+                                s.stddev,
+                            ]
+                            .iter()
+                            .map(|v| format!("{:.2}", v)),
+                        );
+                    }
+                    None => {
+                        record.push("0".to_string());
+                        record.extend(std::iter::repeat_n(String::new(), 10));
+                    }
+                }
+                wrt.write_record(&record)?;
+            }
+        }
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(Summary::of(Vec::<i8>::new()), None);
+        let summary = Summary::of((1..=101).map(|v| v as f64)).unwrap();
+        assert_eq!(summary.count, 101);
+        assert_eq!((summary.min, summary.max), (1.0, 101.0));
+        assert_eq!(
+            (summary.p5, summary.median, summary.p99),
+            (6.0, 51.0, 100.0)
+        );
+        assert_eq!(summary.mean, 51.0);
+
+        let summary = Summary::of([2i8, 4, 4, 4, 5, 5, 7, 9]).unwrap();
+        assert_eq!(summary.stddev, 2.0);
+        assert_eq!(summary.p25, 4.0);
+        assert_eq!(summary.median, 4.5);
+    }
+}
@@ -0,0 +1,528 @@
+//! Parser of the raw OVH weather map SVG files, producing an `OvhData` directly.
+//! This is a port of `weathermap_parse.py`, with the same association heuristics
+//! and the same sanity checks, which are reported as `SvgError`s instead of assertions.
+
+use crate::{Link, OvhData, Router};
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Maximum difference between the distances of the two labels of a link to their link end.
+const MAX_LABEL_DISTANCE: f64 = 100.0;
+
+/// Reason why an SVG weather map could not be turned into an `OvhData`.
+/// Every variant carries the path of the faulty file.
+#[derive(Debug)]
+pub enum SvgError {
+    /// The file could not be opened or read.
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    /// The file is not valid XML.
+    Xml {
+        path: String,
+        source: roxmltree::Error,
+    },
+    /// The document has no `<svg>` element.
+    MissingSvgRoot { path: String },
+    /// An element lacks a required attribute, or its value cannot be parsed.
+    InvalidAttribute {
+        path: String,
+        element: String,
+        attribute: &'static str,
+        value: Option<String>,
+    },
+    /// A `labellink` text is not a percentage.
+    InvalidLoad { path: String, text: String },
+    /// A load is outside of `0..=100`.
+    LoadOutOfRange { path: String, load: i64 },
+    /// A `labellink` text appears before the arrows of its link.
+    LoadWithoutLink { path: String, text: String },
+    /// A `node` text appears without a preceding `node` rectangle.
+    LabelWithoutBox { path: String, text: String },
+    /// There are not enough routers or labels left to associate with a link end.
+    Unmatched {
+        path: String,
+        what: &'static str,
+        link_index: usize,
+    },
+    /// The two labels of a link are too far apart from their respective link end.
+    LabelTooFar {
+        path: String,
+        router: String,
+        peer: String,
+        distance: f64,
+    },
+    /// A router has no link at all.
+    IsolatedRouter { path: String, router: String },
+}
+
+impl SvgError {
+    /// Path of the file that triggered the error.
+    pub fn path(&self) -> &str {
+        match self {
+            SvgError::Io { path, .. }
+            | SvgError::Xml { path, .. }
+            | SvgError::MissingSvgRoot { path }
+            | SvgError::InvalidAttribute { path, .. }
+            | SvgError::InvalidLoad { path, .. }
+            | SvgError::LoadOutOfRange { path, .. }
+            | SvgError::LoadWithoutLink { path, .. }
+            | SvgError::LabelWithoutBox { path, .. }
+            | SvgError::Unmatched { path, .. }
+            | SvgError::LabelTooFar { path, .. }
+            | SvgError::IsolatedRouter { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for SvgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SvgError::Io { path, source } => write!(f, "{}: cannot read file: {}", path, source),
+            SvgError::Xml { path, source } => write!(f, "{}: invalid XML: {}", path, source),
+            SvgError::MissingSvgRoot { path } => write!(f, "{}: no <svg> element", path),
+            SvgError::InvalidAttribute {
+                path,
+                element,
+                attribute,
+                value,
+            } => write!(
+                f,
+                "{}: <{}> has an invalid `{}` attribute: {:?}",
+                path, element, attribute, value
+            ),
+            SvgError::InvalidLoad { path, text } => {
+                write!(f, "{}: invalid link load: {:?}", path, text)
+            }
+            SvgError::LoadOutOfRange { path, load } => {
+                write!(f, "{}: link load out of range: {}", path, load)
+            }
+            SvgError::LoadWithoutLink { path, text } => {
+                write!(
+                    f,
+                    "{}: link load {:?} is not preceded by a link",
+                    path, text
+                )
+            }
+            SvgError::LabelWithoutBox { path, text } => {
+                write!(f, "{}: label {:?} is not preceded by its box", path, text)
+            }
+            SvgError::Unmatched {
+                path,
+                what,
+                link_index,
+            } => write!(f, "{}: no {} left for link {}", path, what, link_index),
+            SvgError::LabelTooFar {
+                path,
+                router,
+                peer,
+                distance,
+            } => write!(
+                f,
+                "{}: labels of the link {} -- {} are too far apart: {}",
+                path, router, peer, distance
+            ),
+            SvgError::IsolatedRouter { path, router } => {
+                write!(f, "{}: router {} has no link", path, router)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SvgError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SvgError::Io { source, .. } => Some(source),
+            SvgError::Xml { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+type Point = (f64, f64);
+/// First and last points of an arrow polygon.
+type Arrow = (Point, Point);
+
+/// Axis-aligned box of a router or of a link label.
+#[derive(Debug, Clone)]
+struct SvgBox {
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+    name: String,
+}
+
+impl SvgBox {
+    /// Distance between the box, extended by `border` on each side, and a point.
+    /// Routers are extended by one pixel to compensate for the rounding errors.
+    fn distance(&self, border: f64, (px, py): Point) -> f64 {
+        let (x1, y1) = (self.x - border, self.y - border);
+        let (x2, y2) = (self.x + self.w + border, self.y + self.h + border);
+        let dx = (x1 - px).max(0.0).max(px - x2);
+        let dy = (y1 - py).max(0.0).max(py - y2);
+        (dx * dx + dy * dy).sqrt()
+    }
+}
+
+/// A link between two routers, as read from its two arrows and two load texts.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct SvgLink {
+    arrow1: Option<Arrow>,
+    arrow2: Option<Arrow>,
+    load1: Option<u32>,
+    load2: Option<u32>,
+}
+
+impl SvgLink {
+    fn middle(arrow: Option<Arrow>) -> Point {
+        match arrow {
+            Some(((x1, y1), (x2, y2))) => ((x1 + x2) / 2.0, (y1 + y2) / 2.0),
+            None => (-1.0, -1.0),
+        }
+    }
+
+    fn p1(&self) -> Point {
+        SvgLink::middle(self.arrow1)
+    }
+
+    fn p2(&self) -> Point {
+        SvgLink::middle(self.arrow2)
+    }
+}
+
+fn has_class(node: &roxmltree::Node, class: &str) -> bool {
+    node.attribute("class")
+        .map(|c| c.split_whitespace().any(|c| c == class))
+        .unwrap_or(false)
+}
+
+fn node_text(node: &roxmltree::Node) -> String {
+    node.descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect()
+}
+
+fn attribute_f64(
+    path: &str,
+    node: &roxmltree::Node,
+    attribute: &'static str,
+) -> Result<f64, SvgError> {
+    let value = node.attribute(attribute);
+    value
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .ok_or_else(|| SvgError::InvalidAttribute {
+            path: path.to_string(),
+            element: node.tag_name().name().to_string(),
+            attribute,
+            value: value.map(|v| v.to_string()),
+        })
+}
+
+fn parse_box(path: &str, node: &roxmltree::Node, name: String) -> Result<SvgBox, SvgError> {
+    Ok(SvgBox {
+        x: attribute_f64(path, node, "x")?,
+        y: attribute_f64(path, node, "y")?,
+        w: attribute_f64(path, node, "width")?,
+        h: attribute_f64(path, node, "height")?,
+        name,
+    })
+}
+
+fn parse_load(path: &str, text: &str) -> Result<u32, SvgError> {
+    let trimmed = text.trim();
+    let load = trimmed
+        .strip_suffix('%')
+        .unwrap_or(trimmed)
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| SvgError::InvalidLoad {
+            path: path.to_string(),
+            text: text.to_string(),
+        })?;
+    if !(0..=100).contains(&load) {
+        return Err(SvgError::LoadOutOfRange {
+            path: path.to_string(),
+            load,
+        });
+    }
+    Ok(load as u32)
+}
+
+/// Returns the first and the last point of an arrow polygon.
+fn parse_arrow(path: &str, node: &roxmltree::Node) -> Result<Arrow, SvgError> {
+    let points = node.attribute("points");
+    let invalid = || SvgError::InvalidAttribute {
+        path: path.to_string(),
+        element: node.tag_name().name().to_string(),
+        attribute: "points",
+        value: points.map(|p| p.to_string()),
+    };
+    let parse_point = |point: &str| -> Option<Point> {
+        let (x, y) = point.split_once(',')?;
+        Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+    };
+    let points = points
+        .ok_or_else(invalid)?
+        .split_whitespace()
+        .collect::<Vec<&str>>();
+    let first = points
+        .first()
+        .and_then(|p| parse_point(p))
+        .ok_or_else(invalid)?;
+    let last = points
+        .last()
+        .and_then(|p| parse_point(p))
+        .ok_or_else(invalid)?;
+    Ok((first, last))
+}
+
+/// Index of the element of `candidates` closest to `point`.
+fn closest(candidates: &[&SvgBox], border: f64, point: Point) -> Option<usize> {
+    candidates
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            a.distance(border, point)
+                .total_cmp(&b.distance(border, point))
+        })
+        .map(|(idx, _)| idx)
+}
+
+/// Parses the content of an SVG weather map. `path` is only used to report errors.
+pub fn parse_svg_str(
+    path: &str,
+    content: &str,
+    timestamp: NaiveDateTime,
+) -> Result<OvhData, SvgError> {
+    let document = roxmltree::Document::parse(content).map_err(|source| SvgError::Xml {
+        path: path.to_string(),
+        source,
+    })?;
+    let svg = document
+        .descendants()
+        .find(|n| n.has_tag_name("svg"))
+        .ok_or_else(|| SvgError::MissingSvgRoot {
+            path: path.to_string(),
+        })?;
+
+    let mut links: Vec<SvgLink> = Vec::new();
+    let mut nodes: Vec<SvgBox> = Vec::new();
+    let mut labels: Vec<SvgBox> = Vec::new();
+
+    let mut link: Option<SvgLink> = None;
+    let mut label: Option<SvgBox> = None;
+    for element in svg.children().filter(|n| n.is_element()) {
+        match element.tag_name().name() {
+            "text" if has_class(&element, "labellink") => {
+                let text = node_text(&element);
+                let current = link.as_mut().ok_or_else(|| SvgError::LoadWithoutLink {
+                    path: path.to_string(),
+                    text: text.to_string(),
+                })?;
+                if current.load1.is_none() {
+                    current.load1 = Some(parse_load(path, &text)?);
+                } else {
+                    current.load2 = Some(parse_load(path, &text)?);
+                    let current = link.take().unwrap();
+                    // Avoid duplicate lines
+                    if !links.contains(&current) {
+                        links.push(current);
+                    }
+                }
+            }
+            "text" if has_class(&element, "node") => {
+                let text = node_text(&element);
+                let mut current = label.take().ok_or_else(|| SvgError::LabelWithoutBox {
+                    path: path.to_string(),
+                    text: text.to_string(),
+                })?;
+                current.name = text;
+                labels.push(current);
+            }
+            "rect" if has_class(&element, "labellink") => (),
+            "rect" if has_class(&element, "object") || has_class(&element, "object_null") => {
+                let name = element
+                    .attribute("alt")
+                    .ok_or_else(|| SvgError::InvalidAttribute {
+                        path: path.to_string(),
+                        element: "rect".to_string(),
+                        attribute: "alt",
+                        value: None,
+                    })?;
+                nodes.push(parse_box(path, &element, name.to_string())?);
+            }
+            "rect" if has_class(&element, "node") => {
+                label = Some(parse_box(path, &element, String::new())?);
+            }
+            // Arrow representing one direction of the link
+            "polygon" => {
+                let arrow = parse_arrow(path, &element)?;
+                match link.as_mut() {
+                    None => {
+                        link = Some(SvgLink {
+                            arrow1: Some(arrow),
+                            ..Default::default()
+                        })
+                    }
+                    Some(current) => current.arrow2 = Some(arrow),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Associate each end of the links with the closest router and the closest free label
+    let mut available_labels: Vec<usize> = (0..labels.len()).collect();
+    let mut router_links: Vec<Vec<(String, u32, usize)>> = vec![Vec::new(); nodes.len()];
+    for (link_index, l) in links.iter().enumerate() {
+        let unmatched = |what: &'static str| SvgError::Unmatched {
+            path: path.to_string(),
+            what,
+            link_index,
+        };
+        let (p1, p2) = (l.p1(), l.p2());
+
+        let all_nodes = nodes.iter().collect::<Vec<&SvgBox>>();
+        let n1 = closest(&all_nodes, 1.0, p1).ok_or_else(|| unmatched("router"))?;
+        let other_nodes = (0..nodes.len())
+            .filter(|&i| i != n1)
+            .collect::<Vec<usize>>();
+        let n2 = closest(
+            &other_nodes
+                .iter()
+                .map(|&i| &nodes[i])
+                .collect::<Vec<&SvgBox>>(),
+            1.0,
+            p2,
+        )
+        .map(|i| other_nodes[i])
+        .ok_or_else(|| unmatched("router"))?;
+
+        let free = available_labels
+            .iter()
+            .map(|&i| &labels[i])
+            .collect::<Vec<&SvgBox>>();
+        let lb1 =
+            available_labels.remove(closest(&free, 0.0, p1).ok_or_else(|| unmatched("label"))?);
+        let free = available_labels
+            .iter()
+            .map(|&i| &labels[i])
+            .collect::<Vec<&SvgBox>>();
+        let lb2 =
+            available_labels.remove(closest(&free, 0.0, p2).ok_or_else(|| unmatched("label"))?);
+
+        let distance = labels[lb2].distance(1.0, p2) - labels[lb1].distance(1.0, p1);
+        if distance.abs() >= MAX_LABEL_DISTANCE {
+            return Err(SvgError::LabelTooFar {
+                path: path.to_string(),
+                router: nodes[n1].name.to_string(),
+                peer: nodes[n2].name.to_string(),
+                distance,
+            });
+        }
+
+        router_links[n1].push((labels[lb1].name.to_string(), l.load1.unwrap_or(0), n2));
+        router_links[n2].push((labels[lb2].name.to_string(), l.load2.unwrap_or(0), n1));
+    }
+
+    // Sanity check: each router should at least have a link.
+    // Empirically we see that no router is isolated in the network
+    if let Some(idx) = router_links.iter().position(|l| l.is_empty()) {
+        return Err(SvgError::IsolatedRouter {
+            path: path.to_string(),
+            router: nodes[idx].name.to_string(),
+        });
+    }
+
+    let mut routers: HashMap<String, Router> = HashMap::new();
+    for (node, node_links) in nodes.iter().zip(router_links) {
+        let router = routers
+            .entry(node.name.to_string())
+            .or_insert_with(|| Router {
+                name: node.name.to_string(),
+                peers: HashMap::new(),
+            });
+        for (label, load, peer) in node_links {
+            router
+                .peers
+                .entry(nodes[peer].name.to_string())
+                .or_default()
+                .push(Link { label, load });
+        }
+    }
+
+    Ok(OvhData {
+        timestamp,
+        data: routers,
+    })
+}
+
+/// Parses an SVG weather map file.
+pub fn parse_svg(filepath: &str, timestamp: NaiveDateTime) -> Result<OvhData, SvgError> {
+    let content = std::fs::read_to_string(filepath).map_err(|source| SvgError::Io {
+        path: filepath.to_string(),
+        source,
+    })?;
+    parse_svg_str(filepath, &content, timestamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_routers(load: &str) -> String {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+  <rect class="object" alt="rbx-g1" x="0" y="0" width="50" height="20"/>
+  <rect class="object" alt="FRANCE-IX" x="300" y="0" width="50" height="20"/>
+  <polygon points="60,8 70,10 60,12"/>
+  <polygon points="290,8 280,10 290,12"/>
+  <text class="labellink">{}</text>
+  <text class="labellink">7%</text>
+  <rect class="node" x="60" y="20" width="10" height="5"/>
+  <text class="node">#1</text>
+  <rect class="node" x="280" y="20" width="10" height="5"/>
+  <text class="node">#2</text>
+</svg>"#,
+            load
+        )
+    }
+
+    #[test]
+    fn test_parse_two_routers() {
+        let data =
+            parse_svg_str("test.svg", &two_routers("42%"), NaiveDateTime::default()).unwrap();
+        assert_eq!(data.data.len(), 2);
+        let links = &data.data["rbx-g1"].peers["FRANCE-IX"];
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].label, "#1");
+        assert_eq!(links[0].load, 42);
+        let links = &data.data["FRANCE-IX"].peers["rbx-g1"];
+        assert_eq!(links[0].label, "#2");
+        assert_eq!(links[0].load, 7);
+    }
+
+    #[test]
+    fn test_load_out_of_range() {
+        let err =
+            parse_svg_str("test.svg", &two_routers("142%"), NaiveDateTime::default()).unwrap_err();
+        assert!(matches!(err, SvgError::LoadOutOfRange { load: 142, .. }));
+    }
+
+    #[test]
+    fn test_isolated_router() {
+        let svg = two_routers("42%").replace(
+            "</svg>",
+            r#"<rect class="object" alt="lonely" x="1000" y="1000" width="5" height="5"/></svg>"#,
+        );
+        let err = parse_svg_str("test.svg", &svg, NaiveDateTime::default()).unwrap_err();
+        match err {
+            SvgError::IsolatedRouter { router, .. } => assert_eq!(router, "lonely"),
+            e => panic!("Unexpected error: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,281 @@
+//! Coverage of the data collection over time.
+//!
+//! Snapshots are collected every 5 minutes; two consecutive snapshots further apart than
+//! `max_distance` (6 minutes by default, as in `script/compute_timeline.py`) delimit a gap
+//! in the data. The timeline gives the continuous collection intervals, the gaps, the
+//! distances between consecutive snapshots and the coverage of each day, so that analyses
+//! can annotate or skip the periods with missing data.
+
+use crate::FileMetadata;
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use csv::Writer;
+use std::error::Error;
+use std::io::Write;
+
+/// Default maximal distance between two snapshots of a continuous interval, in seconds.
+pub const DEFAULT_MAX_DISTANCE: i64 = 6 * 60;
+
+/// Period between two snapshots, both included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+impl Interval {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// Timestamps of the snapshots of a map, with their continuous intervals.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    max_distance: Duration,
+    timestamps: Vec<NaiveDateTime>,
+    intervals: Vec<Interval>,
+}
+
+impl Timeline {
+    /// Timeline of snapshots taken at `timestamps`, in any order.
+    pub fn new<I>(timestamps: I, max_distance: Duration) -> Timeline
+    where
+        I: IntoIterator<Item = NaiveDateTime>,
+    {
+        let mut timestamps = timestamps.into_iter().collect::<Vec<NaiveDateTime>>();
+        timestamps.sort();
+        timestamps.dedup();
+
+        let mut intervals: Vec<Interval> = Vec::new();
+        for &t in timestamps.iter() {
+            match intervals.last_mut() {
+                Some(last) if t - last.end <= max_distance => last.end = t,
+                _ => intervals.push(Interval { start: t, end: t }),
+            }
+        }
+        Timeline {
+            max_distance,
+            timestamps,
+            intervals,
+        }
+    }
+
+    /// Timeline of the snapshot files, with the default maximal distance.
+    pub fn from_files(files: &[FileMetadata]) -> Timeline {
+        Timeline::new(
+            files.iter().map(|f| f.timestamp),
+            Duration::seconds(DEFAULT_MAX_DISTANCE),
+        )
+    }
+
+    pub fn max_distance(&self) -> Duration {
+        self.max_distance
+    }
+
+    /// Sorted timestamps of the snapshots.
+    pub fn timestamps(&self) -> &[NaiveDateTime] {
+        &self.timestamps
+    }
+
+    /// Continuous collection intervals, in order.
+    pub fn intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+
+    /// Periods without data, from the last snapshot before each gap to the first one after.
+    pub fn gaps(&self) -> Vec<Interval> {
+        self.intervals
+            .windows(2)
+            .map(|w| Interval {
+                start: w[0].end,
+                end: w[1].start,
+            })
+            .collect()
+    }
+
+    /// Distances between consecutive snapshots.
+    pub fn distances(&self) -> Vec<Duration> {
+        self.timestamps.windows(2).map(|w| w[1] - w[0]).collect()
+    }
+
+    /// Whether `t` lies within a continuous collection interval.
+    pub fn covers(&self, t: NaiveDateTime) -> bool {
+        let idx = self.intervals.partition_point(|i| i.end < t);
+        self.intervals.get(idx).is_some_and(|i| i.start <= t)
+    }
+
+    /// Gap containing `t`, if any.
+    pub fn gap_at(&self, t: NaiveDateTime) -> Option<Interval> {
+        let idx = self.intervals.partition_point(|i| i.end < t);
+        if idx == 0 || idx == self.intervals.len() || self.intervals[idx].start <= t {
+            return None;
+        }
+        Some(Interval {
+            start: self.intervals[idx - 1].end,
+            end: self.intervals[idx].start,
+        })
+    }
+
+    /// Fraction of each day, from the first snapshot to the last one, covered by the intervals.
+    pub fn daily_coverage(&self) -> Vec<(NaiveDate, f64)> {
+        let (first, last) = match (self.timestamps.first(), self.timestamps.last()) {
+            (Some(first), Some(last)) => (first.date(), last.date()),
+            _ => return Vec::new(),
+        };
+        let day = Duration::days(1);
+        let mut coverage = Vec::new();
+        let mut intervals = self.intervals.iter().peekable();
+        let mut date = first;
+        while date <= last {
+            let start = NaiveDateTime::new(date, NaiveTime::MIN);
+            let end = start + day;
+            let mut covered = Duration::zero();
+            // Intervals are sorted: skip the ones over before this day
+            while intervals.peek().is_some_and(|i| i.end < start) {
+                intervals.next();
+            }
+            for interval in intervals.clone().take_while(|i| i.start < end) {
+                covered += interval.end.min(end) - interval.start.max(start);
+            }
+            coverage.push((
+                date,
+                covered.num_seconds() as f64 / day.num_seconds() as f64,
+            ));
+            date = date.succ_opt().unwrap();
+        }
+        coverage
+    }
+}
+
+/// Writes the intervals of each timeline as `map,start,end,duration`, with headers,
+/// unix timestamps and durations in seconds.
+pub fn write_intervals_csv<W: Write>(
+    timelines: &[(String, Timeline)],
+    wrt: W,
+) -> Result<(), Box<dyn Error>> {
+    write_periods_csv(
+        timelines
+            .iter()
+            .map(|(map, t)| (map, t.intervals().to_vec())),
+        wrt,
+    )
+}
+
+/// Writes the gaps of each timeline as `map,start,end,duration`, with headers,
+/// unix timestamps and durations in seconds.
+pub fn write_gaps_csv<W: Write>(
+    timelines: &[(String, Timeline)],
+    wrt: W,
+) -> Result<(), Box<dyn Error>> {
+    write_periods_csv(timelines.iter().map(|(map, t)| (map, t.gaps())), wrt)
+}
+
+fn write_periods_csv<'a, I, W>(periods: I, wrt: W) -> Result<(), Box<dyn Error>>
+where
+    I: Iterator<Item = (&'a String, Vec<Interval>)>,
+    W: Write,
+{
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record(["map", "start", "end", "duration"])?;
+    for (map, intervals) in periods {
+        for interval in intervals {
+            wrt.write_record([
+                map.to_string(),
+                interval.start.and_utc().timestamp().to_string(),
+                interval.end.and_utc().timestamp().to_string(),
+                interval.duration().num_seconds().to_string(),
+            ])?;
+        }
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+/// Writes the distances between consecutive snapshots of each timeline as `map,distance`,
+/// with headers and distances in seconds.
+pub fn write_distances_csv<W: Write>(
+    timelines: &[(String, Timeline)],
+    wrt: W,
+) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record(["map", "distance"])?;
+    for (map, timeline) in timelines {
+        for distance in timeline.distances() {
+            wrt.write_record([map.to_string(), distance.num_seconds().to_string()])?;
+        }
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+/// Writes the daily coverage of each timeline as `map,date,coverage`, with headers,
+/// dates as `YYYY-MM-DD` and the coverage in percent.
+pub fn write_coverage_csv<W: Write>(
+    timelines: &[(String, Timeline)],
+    wrt: W,
+) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record(["map", "date", "coverage"])?;
+    for (map, timeline) in timelines {
+        for (date, coverage) in timeline.daily_coverage() {
+            wrt.write_record([
+                map.to_string(),
+                date.to_string(),
+                format!("{:.2}", coverage * 100.0),
+            ])?;
+        }
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn t(s: i64) -> NaiveDateTime {
+        DateTime::from_timestamp(s, 0).unwrap().naive_utc()
+    }
+
+    #[test]
+    fn test_timeline() {
+        // Day 0: every 5 minutes from 0 to 12:00, then a gap until day 1 at 06:00
+        let day = 86400;
+        let mut timestamps = (0..=144).map(|i| i * 300).collect::<Vec<i64>>();
+        timestamps.extend([day + 6 * 3600, day + 6 * 3600 + 300, day + 6 * 3600 + 300]);
+        let timeline = Timeline::new(
+            timestamps.into_iter().rev().map(t),
+            Duration::seconds(DEFAULT_MAX_DISTANCE),
+        );
+
+        assert_eq!(timeline.timestamps().len(), 147);
+        assert_eq!(
+            timeline.intervals(),
+            &[
+                Interval {
+                    start: t(0),
+                    end: t(43200)
+                },
+                Interval {
+                    start: t(day + 21600),
+                    end: t(day + 21900)
+                },
+            ]
+        );
+        assert_eq!(timeline.gaps()[0].duration(), Duration::hours(18));
+        assert_eq!(
+            timeline.distances().iter().max(),
+            Some(&Duration::hours(18))
+        );
+        assert!(timeline.covers(t(1000)));
+        assert!(!timeline.covers(t(50000)));
+        assert_eq!(timeline.gap_at(t(50000)), Some(timeline.gaps()[0]));
+        assert_eq!(timeline.gap_at(t(1000)), None);
+
+        let coverage = timeline.daily_coverage();
+        assert_eq!(coverage.len(), 2);
+        assert_eq!(coverage[0].1, 0.5);
+        assert_eq!(coverage[1].1, 300.0 / 86400.0);
+    }
+}
@@ -0,0 +1,521 @@
+//! Estimation of the traffic matrix of a snapshot from its link loads (network tomography).
+//!
+//! The weather map gives the load of each link, in percent of its capacity, and the capacities
+//! are assumptions given by the caller. The traffic between the endpoints (the PoPs, and the
+//! external peers for a PoP-to-peer matrix) is estimated in two steps:
+//! 1. a gravity model, where the traffic from `s` to `d` is proportional to the traffic leaving
+//!    `s` and to the traffic entering `d`, both observed on the links at their boundaries;
+//! 2. a non-negative least-squares refinement, so that the traffic routed along the shortest
+//!    paths (in hops, split equally between equal-cost next hops and parallel links) explains
+//!    the observed traffic of each link, while staying close to the gravity estimate. The
+//!    paths go through OVH routers only: an external peer is never a transit.
+//!
+//! The residuals compare the observed loads with the loads given by the estimated matrix.
+
+use crate::graph::{Graph, NodeId};
+use crate::{is_peer_from_name, pop_from_name, Link, OvhData};
+use chrono::NaiveDateTime;
+use csv::Writer;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Endpoints of the traffic matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    /// Traffic between each pair of PoPs.
+    PopToPop,
+    /// Traffic between each PoP and each external peer, in both directions, and between
+    /// each pair of PoPs.
+    PopToPeer,
+}
+
+impl Granularity {
+    /// Endpoint of a router, if it is one.
+    fn endpoint_of<'a>(&self, router: &'a str) -> Option<&'a str> {
+        match (self, is_peer_from_name(router)) {
+            (_, false) => pop_from_name(router),
+            (Granularity::PopToPop, true) => None,
+            (Granularity::PopToPeer, true) => Some(router),
+        }
+    }
+
+    /// Whether the matrix has a demand from `source` to `destination`.
+    fn has_demand(&self, source: &str, destination: &str) -> bool {
+        match self {
+            Granularity::PopToPop => source != destination,
+            Granularity::PopToPeer => {
+                source != destination
+                    && !(is_peer_from_name(source) && is_peer_from_name(destination))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Granularity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Granularity::PopToPop => write!(f, "pop-to-pop"),
+            Granularity::PopToPeer => write!(f, "pop-to-peer"),
+        }
+    }
+}
+
+impl FromStr for Granularity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Granularity, String> {
+        match s {
+            "pop-to-pop" => Ok(Granularity::PopToPop),
+            "pop-to-peer" => Ok(Granularity::PopToPeer),
+            _ => Err(format!(
+                "Unknown granularity {}, expected pop-to-pop or pop-to-peer",
+                s
+            )),
+        }
+    }
+}
+
+/// Estimated traffic from `source` to `destination`, in the unit of the capacities.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Demand {
+    pub source: String,
+    pub destination: String,
+    pub gravity: f64,
+    pub estimate: f64,
+}
+
+/// Link `label` listed by `router` towards `peer`, with its observed and estimated loads (%).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub router: String,
+    pub peer: String,
+    pub label: String,
+    pub capacity: f64,
+    pub load: u32,
+    pub estimated_load: f64,
+}
+
+impl Observation {
+    /// Estimated minus observed load, in percentage points.
+    pub fn residual(&self) -> f64 {
+        self.estimated_load - self.load as f64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrafficMatrix {
+    pub timestamp: NaiveDateTime,
+    pub granularity: Granularity,
+    /// Sorted by source and destination.
+    pub demands: Vec<Demand>,
+    /// Sorted by router and peer, the parallel links in the order of the snapshot.
+    pub observations: Vec<Observation>,
+}
+
+impl TrafficMatrix {
+    /// Root mean square of the residuals, in percentage points.
+    pub fn rmse(&self) -> f64 {
+        let sum = self
+            .observations
+            .iter()
+            .map(|o| o.residual().powi(2))
+            .sum::<f64>();
+        (sum / self.observations.len().max(1) as f64).sqrt()
+    }
+
+    /// Norm of the differences between the estimated and observed traffic, divided by the
+    /// norm of the observed traffic.
+    pub fn relative_error(&self) -> f64 {
+        let (error, observed) =
+            self.observations
+                .iter()
+                .fold((0.0, 0.0), |(error, observed), o| {
+                    let scale = o.capacity / 100.0;
+                    (
+                        error + (o.residual() * scale).powi(2),
+                        observed + (o.load as f64 * scale).powi(2),
+                    )
+                });
+        match observed {
+            o if o > 0.0 => (error / o).sqrt(),
+            _ => 0.0,
+        }
+    }
+}
+
+/// Parameters of the estimation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tomography {
+    pub granularity: Granularity,
+    /// Iterations of the least-squares refinement, 0 to keep the gravity estimate.
+    pub iterations: usize,
+    /// Weight of the distance to the gravity estimate in the least-squares objective.
+    pub prior_weight: f64,
+}
+
+impl Tomography {
+    pub fn new(granularity: Granularity) -> Tomography {
+        Tomography {
+            granularity,
+            iterations: 1000,
+            prior_weight: 0.01,
+        }
+    }
+
+    /// Estimates the traffic matrix of `data`, where `capacity(router, peer, link)` is the
+    /// capacity of a link. Links without a positive capacity are ignored.
+    pub fn estimate<F>(&self, data: &OvhData, capacity: F) -> TrafficMatrix
+    where
+        F: Fn(&str, &str, &Link) -> f64,
+    {
+        let graph = Graph::from_data(data);
+
+        // Observed traffic of each link listed by a router
+        let mut observations = Vec::new();
+        let mut links: HashMap<(NodeId, NodeId), Vec<usize>> = HashMap::new();
+        let mut routers = data.data.values().collect::<Vec<_>>();
+        routers.sort_by(|a, b| a.name.cmp(&b.name));
+        for router in routers {
+            let mut peers = router.peers.iter().collect::<Vec<_>>();
+            peers.sort_by_key(|(peer, _)| *peer);
+            for (peer, peer_links) in peers {
+                for link in peer_links {
+                    let capacity = capacity(&router.name, peer, link);
+                    if capacity <= 0.0 || router.name == *peer {
+                        continue;
+                    }
+                    let (a, b) = (graph.index_of(&router.name), graph.index_of(peer));
+                    links
+                        .entry((a.unwrap(), b.unwrap()))
+                        .or_default()
+                        .push(observations.len());
+                    observations.push(Observation {
+                        router: router.name.to_string(),
+                        peer: peer.to_string(),
+                        label: link.label.to_string(),
+                        capacity,
+                        load: link.load,
+                        estimated_load: 0.0,
+                    });
+                }
+            }
+        }
+        let traffic = |o: &Observation| o.load as f64 * o.capacity / 100.0;
+
+        // Endpoints, with their routers and the traffic leaving and entering them
+        let mut endpoints: BTreeMap<&str, (Vec<NodeId>, f64, f64)> = BTreeMap::new();
+        for (id, node) in graph.nodes().iter().enumerate() {
+            if let Some(endpoint) = self.granularity.endpoint_of(&node.name) {
+                endpoints.entry(endpoint).or_default().0.push(id);
+            }
+        }
+        for observation in observations.iter() {
+            let from = self.granularity.endpoint_of(&observation.router);
+            let to = self.granularity.endpoint_of(&observation.peer);
+            if let (Some(from), Some(to)) = (from, to) {
+                if from != to {
+                    endpoints.get_mut(from).unwrap().1 += traffic(observation);
+                    endpoints.get_mut(to).unwrap().2 += traffic(observation);
+                }
+            }
+        }
+        // Gravity estimate, and the share of each demand on each observed link
+        let mut demands = Vec::new();
+        let mut routing: Vec<Vec<(usize, f64)>> = Vec::new();
+        for (source, (sources, outgoing, _)) in endpoints.iter() {
+            let destinations = endpoints
+                .iter()
+                .filter(|(destination, _)| self.granularity.has_demand(source, destination))
+                .collect::<Vec<_>>();
+            let incoming = destinations
+                .iter()
+                .map(|(_, (_, _, incoming))| incoming)
+                .sum::<f64>();
+            for (destination, (targets, _, entering)) in destinations {
+                let mut shares: BTreeMap<usize, f64> = BTreeMap::new();
+                // The external peers do not carry the traffic between two other endpoints
+                let internal = |n: NodeId| !graph.node(n).external;
+                for (node, edge, fraction) in graph.ecmp_fractions(sources, targets, internal) {
+                    let next = graph.edge(edge).other(node);
+                    if let Some(ids) = links.get(&(node, next)) {
+                        for &id in ids {
+                            *shares.entry(id).or_default() += fraction / ids.len() as f64;
+                        }
+                    }
+                }
+                let gravity = match incoming {
+                    i if i > 0.0 => outgoing * entering / i,
+                    _ => 0.0,
+                };
+                demands.push(Demand {
+                    source: source.to_string(),
+                    destination: destination.to_string(),
+                    gravity,
+                    estimate: gravity,
+                });
+                routing.push(shares.into_iter().collect());
+            }
+        }
+
+        let observed = observations.iter().map(traffic).collect::<Vec<f64>>();
+        let estimates = self.refine(&routing, &observed, &demands);
+        for (demand, estimate) in demands.iter_mut().zip(estimates) {
+            demand.estimate = estimate;
+        }
+        let routed = route(
+            &routing,
+            &demands.iter().map(|d| d.estimate).collect::<Vec<_>>(),
+            observations.len(),
+        );
+        for (observation, traffic) in observations.iter_mut().zip(routed) {
+            observation.estimated_load = traffic * 100.0 / observation.capacity;
+        }
+
+        TrafficMatrix {
+            timestamp: data.timestamp,
+            granularity: self.granularity,
+            demands,
+            observations,
+        }
+    }
+
+    /// Minimizes `|Ax - y|² + w|x - g|²` with `x >= 0` by projected gradient descent, where
+    /// `A` is the `routing` of the demands, `y` the `observed` traffic and `g` the gravity
+    /// estimate. Demands that cross no observed link are left at 0.
+    fn refine(
+        &self,
+        routing: &[Vec<(usize, f64)>],
+        observed: &[f64],
+        demands: &[Demand],
+    ) -> Vec<f64> {
+        let prior = demands
+            .iter()
+            .zip(routing)
+            .map(|(d, shares)| match shares.is_empty() {
+                true => 0.0,
+                false => d.gravity,
+            })
+            .collect::<Vec<f64>>();
+        if self.iterations == 0 || prior.is_empty() {
+            return prior;
+        }
+        let transposed = |residuals: &[f64]| {
+            routing
+                .iter()
+                .map(|shares| shares.iter().map(|&(l, s)| s * residuals[l]).sum::<f64>())
+                .collect::<Vec<f64>>()
+        };
+
+        // Largest eigenvalue of AᵀA by power iteration, for the step size
+        let mut vector = vec![1.0; prior.len()];
+        let mut eigenvalue = 0.0;
+        for _ in 0..50 {
+            let next = transposed(&route(routing, &vector, observed.len()));
+            eigenvalue = next.iter().map(|v| v * v).sum::<f64>().sqrt();
+            if eigenvalue == 0.0 {
+                break;
+            }
+            vector = next.into_iter().map(|v| v / eigenvalue).collect();
+        }
+        let step = 1.0 / (eigenvalue + self.prior_weight);
+
+        let mut estimates = prior.clone();
+        for _ in 0..self.iterations {
+            let residuals = route(routing, &estimates, observed.len())
+                .into_iter()
+                .zip(observed)
+                .map(|(r, o)| r - o)
+                .collect::<Vec<f64>>();
+            let gradient = transposed(&residuals);
+            for ((estimate, gradient), prior) in estimates.iter_mut().zip(gradient).zip(&prior) {
+                let gradient = gradient + self.prior_weight * (*estimate - prior);
+                *estimate = (*estimate - step * gradient).max(0.0);
+            }
+        }
+        // Unrouted demands stay at 0
+        estimates
+            .into_iter()
+            .zip(routing)
+            .map(|(e, shares)| if shares.is_empty() { 0.0 } else { e })
+            .collect()
+    }
+}
+
+/// Traffic of each of the `nb_links` observed links, given the traffic of each demand.
+fn route(routing: &[Vec<(usize, f64)>], demands: &[f64], nb_links: usize) -> Vec<f64> {
+    let mut traffic = vec![0.0; nb_links];
+    for (shares, demand) in routing.iter().zip(demands) {
+        for &(link, share) in shares {
+            traffic[link] += share * demand;
+        }
+    }
+    traffic
+}
+
+/// Writes the demands as `source,destination,gravity,estimate`, with headers.
+pub fn write_matrix_csv<W: Write>(matrix: &TrafficMatrix, wrt: W) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record(["source", "destination", "gravity", "estimate"])?;
+    for demand in matrix.demands.iter() {
+        wrt.write_record([
+            demand.source.to_string(),
+            demand.destination.to_string(),
+            format!("{:.2}", demand.gravity),
+            format!("{:.2}", demand.estimate),
+        ])?;
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+/// Writes the observed and estimated loads of each link as
+/// `router,peer,label,capacity,load,estimated_load,residual`, with headers.
+pub fn write_residuals_csv<W: Write>(matrix: &TrafficMatrix, wrt: W) -> Result<(), Box<dyn Error>> {
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record([
+        "router",
+        "peer",
+        "label",
+        "capacity",
+        "load",
+        "estimated_load",
+        "residual",
+    ])?;
+    for observation in matrix.observations.iter() {
+        wrt.write_record([
+            observation.router.to_string(),
+            observation.peer.to_string(),
+            observation.label.to_string(),
+            observation.capacity.to_string(),
+            observation.load.to_string(),
+            format!("{:.2}", observation.estimated_load),
+            format!("{:.2}", observation.residual()),
+        ])?;
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Router;
+    use chrono::DateTime;
+
+    /// Builds a snapshot from `(router, peer, load)` links, each the only one of its pair.
+    fn snapshot(links: &[(&str, &str, u32)]) -> OvhData {
+        let mut data: HashMap<String, Router> = HashMap::new();
+        for &(router, peer, load) in links {
+            data.entry(router.to_string())
+                .or_insert_with(|| Router {
+                    name: router.to_string(),
+                    peers: HashMap::new(),
+                })
+                .peers
+                .entry(peer.to_string())
+                .or_default()
+                .push(Link {
+                    label: "#1".to_string(),
+                    load,
+                });
+        }
+        OvhData {
+            timestamp: DateTime::from_timestamp(0, 0).unwrap().naive_utc(),
+            data,
+        }
+    }
+
+    /// The line ams-1 - fra-1 - rbx-1, with AMS-IX on ams-1. The loads come from the demands
+    /// ams -> fra: 10, ams -> rbx: 20, fra -> rbx: 5 and rbx -> ams: 15 on 100 Gbps links.
+    fn data() -> OvhData {
+        snapshot(&[
+            ("ams-1", "fra-1", 30),
+            ("fra-1", "rbx-1", 25),
+            ("rbx-1", "fra-1", 15),
+            ("fra-1", "ams-1", 15),
+            ("ams-1", "AMS-IX", 10),
+            ("AMS-IX", "ams-1", 20),
+        ])
+    }
+
+    #[test]
+    fn test_tomography() {
+        let capacity = |_: &str, _: &str, _: &Link| 100.0;
+        let residual = |matrix: &TrafficMatrix, router: &str, peer: &str| {
+            matrix
+                .observations
+                .iter()
+                .find(|o| o.router == router && o.peer == peer)
+                .unwrap()
+                .residual()
+        };
+        let mut tomography = Tomography::new("pop-to-pop".parse().unwrap());
+        tomography.iterations = 0;
+        let gravity = tomography.estimate(&data(), capacity);
+        assert_eq!(gravity.demands.len(), 6);
+        assert_eq!(gravity.observations.len(), 6);
+        assert!(residual(&gravity, "fra-1", "rbx-1") > 10.0);
+
+        tomography.iterations = 1000;
+        let refined = tomography.estimate(&data(), capacity);
+        for (router, peer) in [
+            ("ams-1", "fra-1"),
+            ("fra-1", "rbx-1"),
+            ("rbx-1", "fra-1"),
+            ("fra-1", "ams-1"),
+        ] {
+            assert!(residual(&refined, router, peer).abs() < 0.5);
+        }
+        // The traffic with the peer is not part of a PoP-to-PoP matrix
+        assert_eq!(residual(&refined, "AMS-IX", "ams-1"), -20.0);
+        assert!(refined.relative_error() < gravity.relative_error());
+        // Only the demands from ams cross the link from ams-1 to fra-1
+        let from_ams = refined
+            .demands
+            .iter()
+            .filter(|d| d.source == "ams")
+            .map(|d| d.estimate)
+            .sum::<f64>();
+        assert!((from_ams - 30.0).abs() < 0.5);
+
+        let matrix = Tomography::new(Granularity::PopToPeer).estimate(&data(), capacity);
+        assert_eq!(matrix.demands.len(), 12);
+        assert_eq!(
+            (
+                matrix.demands[0].source.as_str(),
+                matrix.demands[0].destination.as_str()
+            ),
+            ("AMS-IX", "ams")
+        );
+        assert!(residual(&matrix, "AMS-IX", "ams-1").abs() < 0.5);
+    }
+
+    #[test]
+    fn test_no_transit_through_peers() {
+        // ams-1 and fra-1 share AMS-IX, and are also connected through par-1 and lon-1
+        let data = snapshot(&[
+            ("ams-1", "par-1", 10),
+            ("par-1", "lon-1", 10),
+            ("lon-1", "fra-1", 10),
+            ("ams-1", "AMS-IX", 5),
+            ("AMS-IX", "fra-1", 5),
+        ]);
+        let matrix = Tomography::new(Granularity::PopToPop)
+            .estimate(&data, |_: &str, _: &str, _: &Link| 100.0);
+        let observation = |router: &str, peer: &str| {
+            matrix
+                .observations
+                .iter()
+                .find(|o| o.router == router && o.peer == peer)
+                .unwrap()
+        };
+        assert_eq!(observation("ams-1", "AMS-IX").estimated_load, 0.0);
+        assert_eq!(observation("AMS-IX", "fra-1").estimated_load, 0.0);
+        for (router, peer) in [("ams-1", "par-1"), ("par-1", "lon-1"), ("lon-1", "fra-1")] {
+            assert!(observation(router, peer).residual().abs() < 0.5);
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! Detection of the capacity changes between two routers, i.e., persistent changes
+//! in the number of parallel links from a router towards a peer.
+//!
+//! A new number of links is only accepted once it has been observed in `persistence`
+//! consecutive snapshots, so that a link missing from a single snapshot is not taken for a
+//! downgrade. A known pair missing from a snapshot has 0 links: once its removal is
+//! confirmed, it is reported as a change to 0 links and forgotten. For each change, the
+//! average load of the links is computed over the `window` snapshots before and after it.
+
+use crate::OvhData;
+use chrono::prelude::NaiveDateTime;
+use csv::Writer;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::io::Write;
+
+/// A persistent change in the number of parallel links from `router` towards `peer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapacityChange {
+    pub router: String,
+    pub peer: String,
+    /// First snapshot with the new number of links.
+    pub timestamp: NaiveDateTime,
+    pub links_before: usize,
+    pub links_after: usize,
+    /// Average load (%) of the links before and after the change, `None` without links.
+    pub load_before: Option<f64>,
+    pub load_after: Option<f64>,
+}
+
+impl CapacityChange {
+    pub fn is_upgrade(&self) -> bool {
+        self.links_after > self.links_before
+    }
+}
+
+/// A number of links seen recently, not yet confirmed.
+#[derive(Debug)]
+struct Candidate {
+    nb_links: usize,
+    since: NaiveDateTime,
+    nb_snapshots: usize,
+    loads: Vec<f64>,
+}
+
+#[derive(Debug)]
+struct PairState {
+    nb_links: usize,
+    /// Mean load of the links in the last snapshots with `nb_links` links.
+    loads: VecDeque<f64>,
+    candidate: Option<Candidate>,
+    /// Index in `CapacityDetector::changes` of a change whose load after is being measured,
+    /// and the loads measured so far.
+    measuring: Option<(usize, Vec<f64>)>,
+}
+
+/// Finds the capacity changes from snapshots pushed in timestamp order.
+#[derive(Debug)]
+pub struct CapacityDetector {
+    persistence: usize,
+    window: usize,
+    pairs: HashMap<(String, String), PairState>,
+    changes: Vec<CapacityChange>,
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+impl CapacityDetector {
+    /// A change must last `persistence` snapshots, and loads are averaged over `window` snapshots.
+    pub fn new(persistence: usize, window: usize) -> CapacityDetector {
+        CapacityDetector {
+            persistence: persistence.max(1),
+            window: window.max(1),
+            pairs: HashMap::new(),
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, data: &OvhData) {
+        for (router_name, router) in data.data.iter() {
+            for (peer_name, links) in router.peers.iter() {
+                let load = match links.len() {
+                    0 => None,
+                    n => Some(links.iter().map(|l| l.load as f64).sum::<f64>() / n as f64),
+                };
+                self.observe(router_name, peer_name, data.timestamp, links.len(), load);
+            }
+        }
+        // The known pairs missing from the snapshot have no link left
+        let missing = self
+            .pairs
+            .keys()
+            .filter(|(router, peer)| {
+                data.data
+                    .get(router)
+                    .is_none_or(|r| !r.peers.contains_key(peer))
+            })
+            .cloned()
+            .collect::<Vec<(String, String)>>();
+        for (router, peer) in missing {
+            self.observe(&router, &peer, data.timestamp, 0, None);
+        }
+    }
+
+    fn observe(
+        &mut self,
+        router: &str,
+        peer: &str,
+        timestamp: NaiveDateTime,
+        nb_links: usize,
+        load: Option<f64>,
+    ) {
+        let (window, persistence) = (self.window, self.persistence);
+        let state = match self.pairs.get_mut(&(router.to_string(), peer.to_string())) {
+            Some(state) => state,
+            None => {
+                self.pairs.insert(
+                    (router.to_string(), peer.to_string()),
+                    PairState {
+                        nb_links,
+                        loads: load.into_iter().collect(),
+                        candidate: None,
+                        measuring: None,
+                    },
+                );
+                return;
+            }
+        };
+
+        if nb_links == state.nb_links {
+            // A transient change is forgotten
+            state.candidate = None;
+            if let (Some((idx, loads)), Some(load)) = (state.measuring.as_mut(), load) {
+                loads.push(load);
+                if loads.len() >= window {
+                    self.changes[*idx].load_after = mean(loads);
+                    state.measuring = None;
+                }
+            }
+            if let Some(load) = load {
+                state.loads.push_back(load);
+                if state.loads.len() > window {
+                    state.loads.pop_front();
+                }
+            }
+            return;
+        }
+
+        let candidate = match state.candidate.as_mut() {
+            Some(candidate) if candidate.nb_links == nb_links => {
+                candidate.nb_snapshots += 1;
+                candidate.loads.extend(load);
+                candidate
+            }
+            _ => state.candidate.insert(Candidate {
+                nb_links,
+                since: timestamp,
+                nb_snapshots: 1,
+                loads: load.into_iter().collect(),
+            }),
+        };
+        if candidate.nb_snapshots < persistence {
+            return;
+        }
+
+        // The change is confirmed
+        let candidate = state.candidate.take().unwrap();
+        if let Some((idx, loads)) = state.measuring.take() {
+            // The previous change did not last a full window
+            self.changes[idx].load_after = mean(&loads);
+        }
+        let loads_before = state.loads.iter().copied().collect::<Vec<f64>>();
+        self.changes.push(CapacityChange {
+            router: router.to_string(),
+            peer: peer.to_string(),
+            timestamp: candidate.since,
+            links_before: state.nb_links,
+            links_after: nb_links,
+            load_before: mean(&loads_before),
+            load_after: None,
+        });
+        let idx = self.changes.len() - 1;
+        state.nb_links = nb_links;
+        state.loads = candidate.loads.iter().copied().collect();
+        while state.loads.len() > window {
+            state.loads.pop_front();
+        }
+        if candidate.loads.len() >= window {
+            self.changes[idx].load_after = mean(&candidate.loads[..window]);
+        } else {
+            state.measuring = Some((idx, candidate.loads));
+        }
+        if nb_links == 0 {
+            self.pairs.remove(&(router.to_string(), peer.to_string()));
+        }
+    }
+
+    /// All the changes, in chronological order. The load after the last changes is averaged
+    /// over the snapshots available.
+    pub fn finish(mut self) -> Vec<CapacityChange> {
+        for state in self.pairs.values_mut() {
+            if let Some((idx, loads)) = state.measuring.take() {
+                self.changes[idx].load_after = mean(&loads);
+            }
+        }
+        self.changes.sort_by(|a, b| {
+            (a.timestamp, &a.router, &a.peer).cmp(&(b.timestamp, &b.router, &b.peer))
+        });
+        self.changes
+    }
+}
+
+/// Writes the changes as `timestamp,router,peer,links_before,links_after,load_before,load_after`,
+/// with headers. Unknown loads are left empty.
+pub fn write_changes_csv<W: Write>(
+    changes: &[CapacityChange],
+    wrt: W,
+) -> Result<(), Box<dyn Error>> {
+    let load = |l: Option<f64>| l.map(|l| format!("{:.2}", l)).unwrap_or_default();
+    let mut wrt = Writer::from_writer(wrt);
+    wrt.write_record([
+        "timestamp",
+        "router",
+        "peer",
+        "links_before",
+        "links_after",
+        "load_before",
+        "load_after",
+    ])?;
+    for change in changes {
+        wrt.write_record([
+            change.timestamp.and_utc().timestamp().to_string(),
+            change.router.to_string(),
+            change.peer.to_string(),
+            change.links_before.to_string(),
+            change.links_after.to_string(),
+            load(change.load_before),
+            load(change.load_after),
+        ])?;
+    }
+    wrt.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Link, Router};
+    use chrono::DateTime;
+
+    fn data(t: i64, loads: &[u32]) -> OvhData {
+        let links = loads
+            .iter()
+            .enumerate()
+            .map(|(i, &load)| Link {
+                label: format!("#{}", i),
+                load,
+            })
+            .collect();
+        let router = Router {
+            name: "a".to_string(),
+            peers: HashMap::from([("b".to_string(), links)]),
+        };
+        OvhData {
+            timestamp: DateTime::from_timestamp(t, 0).unwrap().naive_utc(),
+            data: HashMap::from([("a".to_string(), router)]),
+        }
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let mut detector = CapacityDetector::new(2, 2);
+        let snapshots: &[&[u32]] = &[
+            &[80, 80],
+            &[90, 90],
+            // A link missing once is not a downgrade
+            &[90],
+            &[80, 80],
+            // Upgrade
+            &[60, 60, 0],
+            &[50, 50, 20],
+            &[40, 40, 40],
+        ];
+        for (i, loads) in snapshots.iter().enumerate() {
+            detector.push(&data(i as i64 * 300, loads));
+        }
+        let changes = detector.finish();
+        assert_eq!(changes.len(), 1);
+        let change = &changes[0];
+        assert!(change.is_upgrade());
+        assert_eq!(change.timestamp.and_utc().timestamp(), 1200);
+        assert_eq!((change.links_before, change.links_after), (2, 3));
+        assert_eq!(change.load_before, Some(85.0));
+        assert_eq!(change.load_after, Some(40.0));
+    }
+
+    #[test]
+    fn test_removal() {
+        let mut detector = CapacityDetector::new(2, 2);
+        let missing = |t: i64| OvhData {
+            timestamp: DateTime::from_timestamp(t, 0).unwrap().naive_utc(),
+            data: HashMap::new(),
+        };
+        detector.push(&data(0, &[50, 50]));
+        // Missing once is not a removal
+        detector.push(&missing(300));
+        detector.push(&data(600, &[60, 60]));
+        detector.push(&missing(900));
+        detector.push(&missing(1200));
+        assert!(detector.pairs.is_empty());
+
+        let changes = detector.finish();
+        assert_eq!(changes.len(), 1);
+        let change = &changes[0];
+        assert_eq!(change.timestamp.and_utc().timestamp(), 900);
+        assert_eq!((change.links_before, change.links_after), (2, 0));
+        assert_eq!((change.load_before, change.load_after), (Some(55.0), None));
+    }
+}